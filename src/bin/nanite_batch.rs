@@ -0,0 +1,257 @@
+use bevy::{app::App, ecs::{system::RunSystemOnce, world::World}};
+use serde::Serialize;
+
+use nanite_dispersion::{resources::{map::MapConfig, sim_clock::{SimClock, SimTick}, sim_rng::SimRng, hex::{HexGrid, NaniteReserve}, field::NaniteField, weather::Weather, replication::ReplicationSettings, dispersion::{DispersionSettings, DispersionMode}, stats::{StatsFile, TickStats}}, systems::game::{sim_plugin::SimTickPlugin, startup_systems::setup_simulation}};
+
+// Runs the nanite simulation without a window for every seed and every combination
+// of swept parameters, writing one summary per run.
+//
+// nanite_batch [map args] --ticks <n> (--seeds <a,b,..> | --runs <n>)
+//     [--sweep <param>=<v1,v2,..>]... [--out <file>]
+//
+// Map args are the game's, see MapConfig::from_args. Sweepable params are coefficient
+// (up to DispersionSettings::MAX_COEFFICIENT), resource_per_nanite, reserve,
+// wind_strength, replication (0/1) and diffusion (0/1), none of them negative
+fn main() {
+    let batch_config = match BatchConfig::from_args() {
+        Ok(batch_config) => batch_config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        },
+    };
+    let mut out = match StatsFile::create(&batch_config.out, RunSummary::CSV_HEADER) {
+        Some(out) => out,
+        None => return,
+    };
+
+    let combinations = batch_config.combinations();
+    let runs = combinations.len() * batch_config.seeds.len();
+    let mut run_count = 0;
+    for params in combinations.iter() {
+        for seed in batch_config.seeds.iter() {
+            run_count += 1;
+            let summary = run(&batch_config.map, *seed, params, batch_config.ticks);
+            println!("Run {}/{} seed {} {}: {} nanites", run_count, runs, seed, summary.params, summary.final_grid_nanites);
+            out.write_line(|| summary.to_csv(), &summary);
+        }
+    }
+    out.flush();
+    println!("Wrote {} runs to {}", runs, batch_config.out);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SweepParam {
+    Coefficient,
+    ResourcePerNanite,
+    Reserve,
+    WindStrength,
+    Replication,
+    Diffusion
+}
+
+impl SweepParam {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "coefficient" => Some(SweepParam::Coefficient),
+            "resource_per_nanite" => Some(SweepParam::ResourcePerNanite),
+            "reserve" => Some(SweepParam::Reserve),
+            "wind_strength" => Some(SweepParam::WindStrength),
+            "replication" => Some(SweepParam::Replication),
+            "diffusion" => Some(SweepParam::Diffusion),
+            _ => None
+        }
+    }
+
+    // Largest value a run makes sense with, the smallest is always 0
+    fn max_value(&self) -> f32 {
+        match self {
+            SweepParam::Coefficient => DispersionSettings::MAX_COEFFICIENT,
+            SweepParam::Replication | SweepParam::Diffusion => 1.0,
+            SweepParam::ResourcePerNanite | SweepParam::Reserve | SweepParam::WindStrength => f32::MAX,
+        }
+    }
+
+    // Overrides the starting value set up by setup_simulation
+    fn apply(&self, world: &mut World, value: f32) {
+        match self {
+            SweepParam::Coefficient => world.resource_mut::<DispersionSettings>().coefficient = value,
+            SweepParam::ResourcePerNanite => world.resource_mut::<ReplicationSettings>().resource_per_nanite = value,
+            SweepParam::Reserve => world.resource_mut::<NaniteReserve>().amount = value,
+            SweepParam::WindStrength => world.resource_mut::<Weather>().wind_strength = value,
+            SweepParam::Replication => world.resource_mut::<ReplicationSettings>().enabled = value != 0.0,
+            SweepParam::Diffusion => world.resource_mut::<DispersionSettings>().mode = if value != 0.0 {
+                DispersionMode::Diffusion
+            } else {
+                DispersionMode::Overflow
+            },
+        }
+    }
+}
+
+struct Sweep {
+    name: String,
+    param: SweepParam,
+    values: Vec<f32>
+}
+
+struct BatchConfig {
+    map: MapConfig,
+    ticks: u64,
+    seeds: Vec<u64>,
+    sweeps: Vec<Sweep>,
+    out: String
+}
+
+impl BatchConfig {
+    const ARGS: [&'static str; 5] = ["--ticks", "--seeds", "--runs", "--sweep", "--out"];
+
+    // Fails on flags neither the batch nor MapConfig::from_args knows and on values that
+    // don't parse, so a typo doesn't quietly run the defaults
+    fn from_args() -> Result<Self, String> {
+        let args: Vec<String> = std::env::args().collect();
+        let mut flags = args.iter().skip(1);
+        while let Some(flag) = flags.next() {
+            if !BatchConfig::ARGS.contains(&flag.as_str()) && !MapConfig::ARGS.contains(&flag.as_str()) {
+                return Err(format!("Unknown argument {}", flag));
+            }
+            if flags.next().is_none() {
+                return Err(format!("Missing value for {}", flag));
+            }
+        }
+
+        let mut batch_config = BatchConfig {
+            map: MapConfig::from_args(),
+            ticks: 100,
+            seeds: vec![0],
+            sweeps: Vec::new(),
+            out: "batch_summary.csv".to_string()
+        };
+        for pair in args.windows(2) {
+            match (pair[0].as_str(), pair[1].as_str()) {
+                ("--ticks", ticks) => match ticks.parse() {
+                    Ok(ticks) => batch_config.ticks = ticks,
+                    Err(err) => return Err(format!("Invalid tick count {}\n{}", ticks, err)),
+                },
+                ("--seeds", seeds) => match seeds.split(',').map(|seed| seed.parse()).collect() {
+                    Ok(seeds) => batch_config.seeds = seeds,
+                    Err(err) => return Err(format!("Invalid seeds {}\n{}", seeds, err)),
+                },
+                ("--runs", runs) => match runs.parse::<u64>() {
+                    Ok(runs) => batch_config.seeds = (0..runs).collect(),
+                    Err(err) => return Err(format!("Invalid run count {}\n{}", runs, err)),
+                },
+                ("--sweep", sweep) => batch_config.sweeps.push(BatchConfig::parse_sweep(sweep)?),
+                ("--out", path) => batch_config.out = path.to_string(),
+                _ => {}
+            }
+        }
+        Ok(batch_config)
+    }
+
+    fn parse_sweep(sweep: &str) -> Result<Sweep, String> {
+        let (name, values) = sweep.split_once('=')
+            .ok_or_else(|| format!("Invalid sweep {}, expected <param>=<v1,v2,..>", sweep))?;
+        let param = SweepParam::from_name(name)
+            .ok_or_else(|| format!("Unknown sweep param {}", name))?;
+        let values = values.split(',')
+            .map(|value| match value.parse::<f32>() {
+                Ok(parsed) if (0.0..=param.max_value()).contains(&parsed) => Ok(parsed),
+                Ok(_) => Err(format!("Sweep value {} for {} is outside 0 to {}", value, name, param.max_value())),
+                Err(err) => Err(format!("Invalid sweep value {} for {}\n{}", value, name, err)),
+            })
+            .collect::<Result<Vec<f32>, String>>()?;
+        Ok(Sweep { name: name.to_string(), param, values })
+    }
+
+    // Every combination of the swept values, a single empty one without sweeps
+    fn combinations(&self) -> Vec<Vec<(&Sweep, f32)>> {
+        self.sweeps.iter().fold(vec![Vec::new()], |combinations, sweep| {
+            combinations.iter()
+                .flat_map(|combination| sweep.values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push((sweep, *value));
+                    combination
+                }))
+                .collect()
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RunSummary {
+    seed: u64,
+    // Swept values as name=value separated by ;
+    params: String,
+    ticks: u64,
+    final_grid_nanites: f32,
+    peak_grid_nanites: f32,
+    mean_grid_nanites: f32,
+    final_reserve: f32,
+    final_full_hexes: usize,
+    final_mean_density: f32,
+    final_max_density: f32
+}
+
+impl RunSummary {
+    const CSV_HEADER: &'static str = "seed,params,ticks,final_grid_nanites,peak_grid_nanites,mean_grid_nanites,final_reserve,final_full_hexes,final_mean_density,final_max_density";
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.seed, self.params, self.ticks, self.final_grid_nanites, self.peak_grid_nanites, self.mean_grid_nanites,
+            self.final_reserve, self.final_full_hexes, self.final_mean_density, self.final_max_density
+        )
+    }
+}
+
+// Same start as the game with the same seed, see spawn_hexagons and setup_simulation
+fn run(map: &MapConfig, seed: u64, params: &[(&Sweep, f32)], ticks: u64) -> RunSummary {
+    let mut app = App::new();
+    app.add_plugins(SimTickPlugin);
+
+    let mut sim_rng = SimRng::new(seed);
+    let (hex_grid, nanite_field) = map.generate(&mut sim_rng);
+    app.insert_resource(hex_grid);
+    app.insert_resource(nanite_field);
+    app.insert_resource(sim_rng);
+    app.init_resource::<SimClock>();
+    app.world.run_system_once(setup_simulation);
+    for (sweep, value) in params {
+        sweep.param.apply(&mut app.world, *value);
+    }
+
+    let mut summary = RunSummary {
+        seed,
+        params: params.iter().map(|(sweep, value)| format!("{}={}", sweep.name, value)).collect::<Vec<String>>().join(";"),
+        ticks,
+        final_grid_nanites: 0.0,
+        peak_grid_nanites: 0.0,
+        mean_grid_nanites: 0.0,
+        final_reserve: 0.0,
+        final_full_hexes: 0,
+        final_mean_density: 0.0,
+        final_max_density: 0.0
+    };
+    for _ in 0..ticks {
+        app.world.resource_mut::<SimClock>().tick += 1;
+        app.world.run_schedule(SimTick);
+
+        let world = &app.world;
+        let stats = TickStats::from_field(
+            world.resource::<SimClock>().tick,
+            world.resource::<HexGrid>(),
+            world.resource::<NaniteField>(),
+            world.resource::<Weather>(),
+            world.resource::<NaniteReserve>()
+        );
+        summary.peak_grid_nanites = summary.peak_grid_nanites.max(stats.grid_nanites);
+        summary.mean_grid_nanites += stats.grid_nanites / ticks as f32;
+        summary.final_grid_nanites = stats.grid_nanites;
+        summary.final_reserve = stats.reserve;
+        summary.final_full_hexes = stats.full_hexes;
+        summary.final_mean_density = stats.mean_density;
+        summary.final_max_density = stats.max_density;
+    }
+    summary
+}
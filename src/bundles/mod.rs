@@ -1,2 +1 @@
-pub mod macc_bundle;
-pub mod hex_bundle;
\ No newline at end of file
+pub mod macc_bundle;
\ No newline at end of file
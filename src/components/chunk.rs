@@ -0,0 +1,13 @@
+use bevy::ecs::component::Component;
+
+// Render entity drawing one chunk of the NaniteField
+#[derive(Component)]
+pub struct ChunkRender {
+    pub chunk: (usize, usize)
+}
+
+// Nanite total written over a hex when zoomed in, see update_value_labels
+#[derive(Component)]
+pub struct HexValueLabel {
+    pub pos: (usize, usize)
+}
@@ -2,6 +2,5 @@ use bevy::ecs::component::Component;
 
 #[derive(Component, Clone)]
 pub enum ClickSignal {
-    Hex,
     Macc
 }
\ No newline at end of file
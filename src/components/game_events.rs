@@ -1,8 +1,35 @@
 use bevy::{ecs::{event::Event, entity::Entity}, math::Vec2};
+use serde::{Serialize, Deserialize};
+
+use crate::resources::{boundary::MapEdge, hex::HexDirection};
+
+use super::{structure::StructureKind, macc::MaccOrder};
 
 #[derive(Event)]
 pub enum GameEvents {
-    HexSelect(Entity),
+    HexSelect((usize, usize)),
     MaccSelect(Entity),
-    MaccMoveOrder(Vec2)
-}
\ No newline at end of file
+    MaccMoveOrder(Vec2),
+    MaccQueueWaypoint(Vec2),
+    MaccOrder(Entity, MaccOrder),
+    BuildStructure((usize, usize), StructureKind)
+}
+
+// Player commands that change the simulation. They are queued in the SimCommandQueue,
+// recorded into the Replay and applied between ticks, see issue_sim_commands
+#[derive(Event, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SimCommand {
+    MaccSelect(u32),
+    MaccMoveOrder((f32, f32)),
+    BuildStructure((usize, usize), StructureKind),
+    ToggleReplication,
+    ToggleDispersionMode,
+    SetDispersionCoefficient(f32),
+    CycleBoundary(MapEdge),
+    CycleEdge((i32, i32), HexDirection),
+    // Steps a filter edge's permeability, see EdgeAttribute::step_filter
+    StepEdgeFilter((i32, i32), HexDirection, i32),
+    MaccDeselect,
+    MaccQueueWaypoint((f32, f32)),
+    MaccOrder(u32, MaccOrder)
+}
@@ -7,10 +7,6 @@ pub struct GridPos {
 }
 
 impl GridPos {
-    pub fn row_is_even(&self) -> bool {
-        self.pos.0 % 2 == 0
-    }
-
     pub fn to_int(&self) -> (i32, i32) {
         (self.pos.0 as i32, self.pos.1 as i32)
     }
@@ -0,0 +1,29 @@
+use super::terrain::Terrain;
+
+#[derive(Debug, Default)]
+pub struct HexResource {
+    pub amount: f32,
+    pub max_amount: f32,
+    pub regen_rate: f32
+}
+
+impl HexResource {
+    pub fn from_terrain(terrain: &Terrain) -> Self {
+        let max_amount = terrain.resource_capacity();
+        Self {
+            amount: max_amount,
+            max_amount,
+            regen_rate: max_amount * 0.01
+        }
+    }
+
+    pub fn consume(&mut self, amount: f32) -> f32 {
+        let consumed = amount.clamp(0.0, self.amount);
+        self.amount -= consumed;
+        consumed
+    }
+
+    pub fn regenerate(&mut self) {
+        self.amount = (self.amount + self.regen_rate).min(self.max_amount);
+    }
+}
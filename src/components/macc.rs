@@ -1,18 +1,139 @@
-use bevy::{ecs::component::Component, math::Vec2};
+use std::fmt::Display;
+use bevy::{ecs::component::Component, math::Vec2, render::color::Color};
+use serde::{Serialize, Deserialize};
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
 pub enum Team {
     A, B
 }
 
+impl From<&Team> for Color {
+    fn from(value: &Team) -> Self {
+        match value {
+            Team::A => Color::rgb(0.2, 0.5, 1.0),
+            Team::B => Color::rgb(1.0, 0.3, 0.2),
+        }
+    }
+}
+
+// Stable id of a MACC, entities differ between runs so replays refer to this
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaccId(pub u32);
+
 #[derive(Component, Debug)]
 pub struct Macc {
+    // Where the simulation has it, moved by move_maccs each SimTick. The Transform
+    // eases towards it between ticks
+    pub position: Vec2,
+    // Radians counterclockwise from up
+    pub heading: f32,
     pub target_position: Vec2,
-    pub turn_radius: f32 // Max angle to turn in degrees
+    // Queued after the target, in order
+    pub waypoints: Vec<Vec2>,
+    // Where Return sends it and its cargo is unloaded
+    pub home_position: Vec2,
+    pub turn_radius: f32, // Max angle to turn in degrees
+    // Max distance moved per step, see Macc::STEPS_PER_TICK
+    pub max_step: f32,
+    // World units per second over the last tick
+    pub speed: f32,
+    // Holding MACCs stay put and ignore move orders
+    pub hold: bool
 }
 
 impl Macc {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            heading: 0.0,
+            target_position: position,
+            waypoints: Vec::new(),
+            home_position: position,
+            turn_radius: 1.0,
+            max_step: 1.0,
+            speed: 0.0,
+            hold: false
+        }
+    }
+
+    // Movement steps in one SimTick, turning and moving up to max_step each
+    pub const STEPS_PER_TICK: u32 = 64;
+
     pub fn in_position(&self, current_location: Vec2) -> bool {
         self.target_position.distance(current_location) <= 1.0
     }
+
+    pub fn order(&mut self, order: MaccOrder, current_location: Vec2) {
+        match order {
+            MaccOrder::Stop => {
+                self.target_position = current_location;
+                self.waypoints.clear();
+            },
+            MaccOrder::Hold => {
+                self.hold = !self.hold;
+                if self.hold {
+                    self.target_position = current_location;
+                }
+            },
+            MaccOrder::Return => {
+                self.hold = false;
+                self.target_position = self.home_position;
+                self.waypoints.clear();
+            },
+        }
+    }
+}
+
+// Orders from the unit panel, see Macc::order
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaccOrder {
+    Stop,
+    // Toggles holding position
+    Hold,
+    Return
+}
+
+impl Display for MaccOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaccOrder::Stop => write!(f, "Stop"),
+            MaccOrder::Hold => write!(f, "Hold"),
+            MaccOrder::Return => write!(f, "Return"),
+        }
+    }
+}
+
+// Wear from nanite exposure and the nanite samples a MACC collects, updated every
+// SimTick by macc_exposure
+#[derive(Component, Debug)]
+pub struct MaccStatus {
+    pub health: f32,
+    pub cargo: f32
+}
+
+impl Default for MaccStatus {
+    fn default() -> Self {
+        Self {
+            health: MaccStatus::MAX_HEALTH,
+            cargo: 0.0
+        }
+    }
+}
+
+impl MaccStatus {
+    pub const MAX_HEALTH: f32 = 100.0;
+    pub const CARGO_CAPACITY: f32 = 50.0;
+    // Health lost per nanite on the MACC's hex each tick
+    pub const DAMAGE_PER_NANITE: f32 = 0.005;
+    // Samples gathered per nanite on the MACC's hex each tick
+    pub const SAMPLES_PER_NANITE: f32 = 0.01;
+
+    pub fn disabled(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    pub fn expose(&mut self, nanites: f32) {
+        self.health = (self.health - nanites * MaccStatus::DAMAGE_PER_NANITE).max(0.0);
+        self.cargo = (self.cargo + nanites * MaccStatus::SAMPLES_PER_NANITE).min(MaccStatus::CARGO_CAPACITY);
+    }
 }
\ No newline at end of file
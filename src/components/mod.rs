@@ -4,4 +4,7 @@ pub mod grid_pos;
 pub mod ui;
 pub mod terrain;
 pub mod macc;
-pub mod game_events;
\ No newline at end of file
+pub mod game_events;
+pub mod hex_resource;
+pub mod structure;
+pub mod chunk;
\ No newline at end of file
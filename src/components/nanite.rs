@@ -1,6 +1,4 @@
-use bevy::ecs::component::Component;
-
-#[derive(Component, Debug)]
+#[derive(Clone, Debug)]
 pub struct Nanite {
     pub nanite_capacity: f32,
     pub nanite_total: f32,
@@ -26,10 +24,8 @@ impl Nanite {
         self.transient_nanites = 0.0;
     }
 
-    pub fn spill(&mut self) -> f32 {
-        let overflow = (self.nanite_total - self.nanite_capacity).max(0.0);
-        self.nanite_total -= overflow;
-        overflow
+    pub fn overflow(&self) -> f32 {
+        (self.nanite_total - self.nanite_capacity).max(0.0)
     }
 
     pub fn wind_pull(&mut self, strength: f32) -> f32 {
@@ -38,6 +34,20 @@ impl Nanite {
         amount
     }
 
+    // Logistic growth towards nanite_capacity, limited by the resource available
+    pub fn replicate(&self, rate: f32, max_growth: f32) -> f32 {
+        let growth = rate * self.nanite_total * (1.0 - self.nanite_total / self.nanite_capacity);
+        growth.clamp(0.0, max_growth.max(0.0))
+    }
+
+    // Takes the fraction from what the hex holds once the transients are applied,
+    // so it can't leave less than nothing whatever else left the hex this tick
+    pub fn scrub(&mut self, fraction: f32) -> f32 {
+        let amount = (self.nanite_total + self.transient_nanites).max(0.0) * fraction.clamp(0.0, 1.0);
+        self.transient_nanites -= amount;
+        amount
+    }
+
     pub fn is_full(&self) -> bool {
         self.nanite_total > self.nanite_capacity
     }
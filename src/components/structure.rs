@@ -0,0 +1,65 @@
+use std::fmt::Display;
+use bevy::ecs::component::Component;
+use serde::{Serialize, Deserialize};
+
+use crate::resources::hex::{HexDirection, EdgeAttribute};
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StructureKind {
+    Emitter,
+    Scrubber,
+    Barrier
+}
+
+impl StructureKind {
+    pub fn cost(&self) -> f32 {
+        match self {
+            StructureKind::Emitter => 100.0,
+            StructureKind::Scrubber => 150.0,
+            StructureKind::Barrier => 50.0,
+        }
+    }
+}
+
+impl Display for StructureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructureKind::Emitter => write!(f, "Emitter"),
+            StructureKind::Scrubber => write!(f, "Scrubber"),
+            StructureKind::Barrier => write!(f, "Barrier"),
+        }
+    }
+}
+
+// Injects a fixed amount of nanites into its hex every tick
+#[derive(Component, Debug)]
+pub struct Emitter {
+    pub rate: f32
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self { rate: 5.0 }
+    }
+}
+
+// Removes a fraction of the nanites of every hex within radius every tick
+#[derive(Component, Debug)]
+pub struct Scrubber {
+    pub radius: u32,
+    pub rate: f32
+}
+
+impl Default for Scrubber {
+    fn default() -> Self {
+        Self { radius: 1, rate: 0.25 }
+    }
+}
+
+// Edges of its hex walled off by the barrier, see HexGrid::edges, with what each edge was
+// before. One per barrier built on the hex, kept on the edges facing most into the wind
+// by barrier_track_wind
+#[derive(Component, Debug, Default)]
+pub struct Barrier {
+    pub edges: Vec<(HexDirection, EdgeAttribute)>
+}
@@ -1,21 +1,34 @@
 use std::fmt::Display;
-use bevy::ecs::component::Component;
 use bevy::render::color::Color;
 use rand::Rng;
 
-#[derive(Component)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Terrain {
     Land, Water
 }
 
 impl Terrain {
-    pub fn from_random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn from_random(rng: &mut impl Rng) -> Self {
         match rng.gen_range(0..=1) {
             0 => Terrain::Land,
             _ => Terrain::Water
         }
     }
+
+    // Logistic growth rate of nanites per tick
+    pub fn replication_rate(&self) -> f32 {
+        match self {
+            Terrain::Land => 0.2,
+            Terrain::Water => 0.05,
+        }
+    }
+
+    pub fn resource_capacity(&self) -> f32 {
+        match self {
+            Terrain::Land => 50.0,
+            Terrain::Water => 20.0,
+        }
+    }
 }
 
 impl Display for Terrain {
@@ -1,4 +1,8 @@
-use bevy::ecs::component::Component;
+use bevy::ecs::{component::Component, entity::Entity};
+
+use crate::resources::{sim_clock::SimSpeed, hex::MapState, stats::StatsMetric};
+
+use super::{structure::StructureKind, macc::MaccOrder};
 
 #[derive(Component)]
 pub struct UICompass;
@@ -9,13 +13,95 @@ pub struct HexPosText;
 pub struct HexTerrainText;
 #[derive(Component)]
 pub struct HexNaniteText;
+#[derive(Component)]
+pub struct HexStructureText;
+#[derive(Component)]
+pub struct BuildFundsText;
+#[derive(Component)]
+pub struct SimClockText;
+
+// History timeline, dragging along the track picks the tick shown on the map
+#[derive(Component)]
+pub struct TimelineTrack;
+#[derive(Component)]
+pub struct TimelineHandle;
+#[derive(Component)]
+pub struct TimelineLiveButton;
+#[derive(Component)]
+pub struct TimelineText;
+
+// Heatmap legend, only shown on the nanite map view
+#[derive(Component)]
+pub struct HeatmapLegend;
+#[derive(Component)]
+pub struct HeatmapLegendTitle;
+// Position along the ramp the swatch shows the color of
+#[derive(Component)]
+pub struct HeatmapLegendSwatch(pub f32);
+// Position along the ramp the label reads the value of
+#[derive(Component)]
+pub struct HeatmapLegendLabel(pub f32);
+
+// Minimap of the whole grid in the current MapState, clicking or dragging on it moves the camera
+#[derive(Component)]
+pub struct Minimap;
+#[derive(Component)]
+pub struct MinimapViewport;
+// Dot for a MACC entity
+#[derive(Component)]
+pub struct MinimapMacc(pub Entity);
 
 #[derive(Component)]
 pub struct RightInfoPane;
+// Line charts of the StatsHistory, shown with K or the map bar button
+#[derive(Component)]
+pub struct ChartsPanel;
+#[derive(Component)]
+pub struct ChartsPanelButton;
+#[derive(Component)]
+pub struct StatsChart(pub StatsMetric);
+#[derive(Component)]
+pub struct StatsChartText(pub StatsMetric);
+// Nanites on the selected hex over the ticks in the FieldHistory
+#[derive(Component)]
+pub struct HexSparkline;
+#[derive(Component)]
+pub struct HexSparklineText;
+// Follows the cursor with details of whatever is under it
+#[derive(Component)]
+pub struct HoverTooltip;
+#[derive(Component)]
+pub struct HoverTooltipText;
+// MACCs standing in the selected hex, in the order they are listed. None until first built
+#[derive(Component, Default)]
+pub struct HexMaccList(pub Option<Vec<Entity>>);
+// Text of a HexMaccList entry
+#[derive(Component)]
+pub struct HexMaccEntry(pub Entity);
+
+// Unit panel for the selected MACC
+#[derive(Component)]
+pub struct MaccInfoPane;
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum MaccInfoText {
+    // Id, team and hex
+    Identity,
+    // Target, waypoints, speed and heading
+    Movement,
+    // Health and cargo
+    Condition
+}
 
 #[derive(Component)]
 pub enum ButtonOnClick {
     InfoPaneClose,
-    MapButtonTerrain,
-    MapButtonNanite
+    MapButton(MapState),
+    FlowOverlay,
+    MaccPaneClose,
+    SelectMacc(Entity),
+    MaccOrder(MaccOrder),
+    Build(StructureKind),
+    SimPause,
+    SimStep,
+    SimSpeed(SimSpeed)
 }
\ No newline at end of file
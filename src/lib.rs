@@ -0,0 +1,4 @@
+pub mod resources;
+pub mod systems;
+pub mod components;
+pub mod bundles;
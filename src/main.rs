@@ -1,24 +1,25 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use components::game_events::GameEvents;
-use resources::{input::GameEntitiesClickable, hex::{MapState, HexGrid}, asset_handles::LoadingStates};
-use systems::{game::{startup_systems::{setup_camera, setup_assets, spawn_hexagons, setup}, continuous_systems::map_state_material_static}, game::{input_systems::{calc_world_coords, on_game_entity_click, keyboard_input, mouse_input, zoom_camera}, startup_systems::create_colliders}, game::continuous_systems::{nanite_material_update, nanite_dispersion, nanite_wind, nanite_introduction, nanite_transient_apply, adjust_wind, game_event_react, move_maccs}, ui::{ui_setup::ui_setup, ui_continuous::{update_compass, ui_game_event_react, ui_button_system, reset_game_entities_clickable, update_nanite_info_pane}}};
-
-mod resources;
-mod systems;
-mod components;
-mod bundles;
+use nanite_dispersion::components::game_events::{GameEvents, SimCommand};
+use nanite_dispersion::resources::{input::{GameEntitiesClickable, SelectedMacc}, hex::{HexGrid, MapState}, asset_handles::LoadingStates, map::MapConfig, field::NaniteField, sim_clock::{SimTick, ApplySimCommands}, sim_rng::SimRng, replay::Replay, history::FieldHistory, stats::{StatsRecorder, StatsHistory}, heatmap::HeatmapSettings, flow::{NaniteFlow, FlowOverlay}, minimap::MinimapLayout};
+use nanite_dispersion::systems::{game::{sim_plugin::SimTickPlugin, startup_systems::{setup_camera, setup_assets, spawn_hexagons, setup, setup_simulation}, continuous_systems::{update_visible_chunks, update_chunk_colors, run_sim_ticks, sync_virtual_time, issue_sim_commands, sim_command_react, save_replay, record_history, record_stats, flush_stats, update_value_labels, clear_value_labels, draw_wind_field, draw_nanite_flow, draw_selection, draw_order_markers, update_minimap}}, game::{input_systems::{calc_world_coords, on_game_entity_click, keyboard_input, edge_scroll_camera, drag_pan_camera, mouse_input, zoom_camera, clamp_camera, camera_focus_input, double_click_focus, update_camera_focus, toggle_replication, edit_edges, dispersion_input, boundary_input, sim_clock_input, replay_input, heatmap_input, map_overlay_input, flow_overlay_input}, startup_systems::create_colliders}, game::continuous_systems::{nanite_transient_apply, game_event_react, move_maccs, ease_macc_transforms, macc_exposure, structure_event_react, draw_structures, draw_edges, draw_boundaries}, ui::{ui_setup::ui_setup, ui_continuous::{update_compass, ui_game_event_react, ui_button_system, reset_game_entities_clickable, update_nanite_info_pane, update_structure_info_pane, update_sim_clock_text, timeline_input, update_timeline, update_heatmap_legend, update_minimap_overlay, minimap_input, update_macc_info_pane, update_hex_macc_list, update_hover_tooltip, charts_panel_input, update_stats_charts, update_hex_sparkline}}};
 
 fn main() {
+    let replay = Replay::from_args(MapConfig::from_args());
 
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(1.0 ))
         .add_event::<GameEvents>()
+        .add_event::<SimCommand>()
+        .insert_resource(replay.map.clone())
+        .insert_resource(SimRng::new(replay.seed))
+        .insert_resource(replay)
         .add_state::<LoadingStates>()
         //Startup
         //Game Systems
         .add_systems(PreStartup, setup)
+        .add_systems(PreStartup, setup_simulation)
         .add_systems(PreStartup, setup_assets)
         .add_systems(Startup, setup_camera)
         .add_systems(OnEnter(LoadingStates::Complete), spawn_hexagons)
@@ -28,54 +29,97 @@ fn main() {
         .add_systems(First, create_colliders.run_if(in_state(LoadingStates::Loading)))
         .add_systems(First, calc_world_coords)
         .add_systems(First, keyboard_input)
+        .add_systems(First, edge_scroll_camera)
+        .add_systems(First, drag_pan_camera)
         .add_systems(First, mouse_input)
+        .add_systems(First, toggle_replication)
+        .add_systems(First, dispersion_input)
+        .add_systems(First, sim_clock_input)
+        .add_systems(First, map_overlay_input.run_if(resource_exists::<MapState>()))
+        .add_systems(First, flow_overlay_input.run_if(resource_exists::<FlowOverlay>()))
+        .add_systems(First, heatmap_input.run_if(resource_exists::<HeatmapSettings>()))
+        .add_systems(First, replay_input.run_if(replay_playback))
+        .add_systems(First, boundary_input.run_if(resource_exists::<HexGrid>()))
         .add_systems(First, ui_button_system.before(reset_game_entities_clickable).run_if(in_state(LoadingStates::Complete)))
+        .add_systems(First, timeline_input.before(reset_game_entities_clickable).run_if(resource_exists::<FieldHistory>()))
+        .add_systems(First, minimap_input.before(reset_game_entities_clickable).run_if(resource_exists::<MinimapLayout>()))
         .add_systems(PreUpdate, zoom_camera)
-        .add_systems(PreUpdate, on_game_entity_click.run_if(game_entities_clickable))
-        .add_systems(FixedUpdate, move_maccs)
-        //Nanite systems
-        .add_systems(Update, ((
-                nanite_introduction,
-                nanite_wind,
-                nanite_dispersion
-            )).run_if(time_passed(1.0))
-        )
+        .add_systems(First, camera_focus_input.run_if(resource_exists::<FieldHistory>()))
+        .add_systems(PreUpdate, double_click_focus.run_if(game_entities_clickable.and_then(resource_exists::<HexGrid>())))
+        .add_systems(PreUpdate, update_camera_focus.after(zoom_camera).before(clamp_camera))
+        .add_systems(PreUpdate, clamp_camera.after(zoom_camera).run_if(resource_exists::<HexGrid>()))
+        .add_systems(PreUpdate, on_game_entity_click.run_if(game_entities_clickable.and_then(resource_exists::<HexGrid>())))
+        .add_systems(PreUpdate, edit_edges.run_if(game_entities_clickable.and_then(edge_editing).and_then(resource_exists::<HexGrid>())))
+        .add_systems(Update, ease_macc_transforms.after(run_sim_ticks))
+        //Nanite systems, one run of SimTick per simulation tick
+        .add_systems(PreUpdate, sync_virtual_time)
+        .add_systems(Update, run_sim_ticks.after(game_event_react).run_if(resource_exists::<NaniteField>()))
+        .add_systems(ApplySimCommands, (issue_sim_commands, (sim_command_react, structure_event_react)).chain())
+        .add_plugins(SimTickPlugin)
+        .add_systems(SimTick, record_history.after(nanite_transient_apply))
+        .add_systems(SimTick, move_maccs.before(macc_exposure))
+        .add_systems(SimTick, macc_exposure.after(nanite_transient_apply).before(record_stats))
+        .add_systems(SimTick, record_stats.after(nanite_transient_apply).run_if(resource_exists::<StatsRecorder>().or_else(resource_exists::<StatsHistory>())))
         .add_systems(Update, game_event_react)
-        .add_systems(Update, adjust_wind.run_if(time_passed(10.0)))
         .add_systems(Update, ui_game_event_react.run_if(in_state(LoadingStates::Complete)))
-        .add_systems(PostUpdate, nanite_transient_apply)
         //Graphics update
-        .add_systems(Last, map_state_material_static.run_if(map_state_changed.and_then(in_state(LoadingStates::Complete))))
-        .add_systems(Last, nanite_material_update.run_if(
-            resource_exists::<MapState>().and_then(|state: Res<MapState>| *state == MapState::Nanite))
+        .add_systems(Last, (
+                update_visible_chunks,
+                update_chunk_colors
+            ).chain().run_if(resource_exists::<NaniteField>())
         )
+        .add_systems(Last, update_value_labels.run_if(resource_exists::<NaniteField>().and_then(value_labels_shown)))
+        .add_systems(Last, clear_value_labels.run_if(not(value_labels_shown)))
+        .add_systems(Last, update_heatmap_legend.run_if(resource_exists::<HeatmapSettings>()))
+        .add_systems(Last, update_minimap.after(update_chunk_colors).run_if(resource_exists::<NaniteField>()))
+        .add_systems(Last, update_minimap_overlay.after(update_minimap).run_if(resource_exists::<MinimapLayout>()))
         .add_systems(Last, update_compass)
+        .add_systems(Last, update_sim_clock_text)
+        .add_systems(Last, update_timeline.run_if(resource_exists::<FieldHistory>()))
+        .add_systems(Last, (draw_structures, draw_edges, draw_boundaries).run_if(resource_exists::<HexGrid>()))
+        .add_systems(Last, draw_nanite_flow.run_if(resource_exists::<NaniteFlow>().and_then(flow_overlay_shown)))
+        .add_systems(Last, (draw_selection, draw_order_markers).run_if(in_state(LoadingStates::Complete)))
+        .add_systems(Last, draw_wind_field.run_if(resource_exists::<FieldHistory>().and_then(wind_overlay_shown)))
         .add_systems(Last, update_nanite_info_pane.run_if(in_state(LoadingStates::Complete).and_then(right_panel_open)))
+        .add_systems(Update, charts_panel_input)
+        .add_systems(Last, update_stats_charts.run_if(resource_exists::<StatsHistory>()))
+        .add_systems(Last, update_hex_sparkline.run_if(resource_exists::<StatsHistory>().and_then(right_panel_open)))
+        .add_systems(Last, update_hover_tooltip.run_if(in_state(LoadingStates::Complete)))
+        .add_systems(Last, update_hex_macc_list.run_if(in_state(LoadingStates::Complete).and_then(right_panel_open)))
+        .add_systems(Last, update_macc_info_pane.run_if(resource_exists::<SelectedMacc>()))
+        .add_systems(Last, update_structure_info_pane.run_if(in_state(LoadingStates::Complete).and_then(right_panel_open)))
         .add_systems(Last, reset_game_entities_clickable)
+        .add_systems(Last, save_replay)
+        .add_systems(Last, flush_stats.run_if(resource_exists::<StatsRecorder>()))
         .run();
 }
 
-fn time_passed(t: f32) -> impl FnMut(Local<f32>, Res<Time>) -> bool {
-    move |mut timer: Local<f32>, time: Res<Time>| {
-        // Tick the timer
-        *timer += time.delta_seconds();
-        // Return true if the timer has passed the time
-        let run  = *timer >= t;
-        if run {
-            *timer = 0.0;
-        }
-        run
+fn replay_playback(replay: Res<Replay>) -> bool {
+    replay.is_playback()
+}
+
+fn value_labels_shown(heatmap_settings: Option<Res<HeatmapSettings>>, map_state: Option<Res<MapState>>) -> bool {
+    match (heatmap_settings, map_state) {
+        (Some(heatmap_settings), Some(map_state)) => heatmap_settings.show_values && map_state.uses_heatmap(),
+        _ => false
     }
 }
 
+fn flow_overlay_shown(flow_overlay: Option<Res<FlowOverlay>>) -> bool {
+    flow_overlay.is_some_and(|flow_overlay| flow_overlay.enabled)
+}
+
+fn wind_overlay_shown(map_state: Res<MapState>) -> bool {
+    *map_state == MapState::Wind
+}
+
 fn game_entities_clickable(clickable: Res<GameEntitiesClickable>) -> bool {
     clickable.0
 }
 
-fn map_state_changed(
-    map_state: Res<MapState>
-) -> bool {
-    map_state.is_changed()
+// Holding shift turns left clicks into edge edits
+fn edge_editing(keys: Res<Input<KeyCode>>) -> bool {
+    keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
 }
 
 fn right_panel_open(hex_grid: Res<HexGrid>) -> bool {
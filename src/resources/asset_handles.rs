@@ -11,7 +11,6 @@ pub enum LoadingStates {
 #[derive(Resource)]
 pub struct AssetHandles {
     pub out_hex_handle: Mesh2dHandle,
-    pub inner_hex_handle: Mesh2dHandle,
     pub macc_image_handle: Handle<Image>,
     pub color_handle_white: Handle<ColorMaterial>,
 }
@@ -21,10 +20,6 @@ impl AssetHandles {
         self.out_hex_handle.clone()
     }
 
-    pub fn get_inner_hex_handle(&self) -> Mesh2dHandle {
-        self.inner_hex_handle.clone()
-    }
-
     pub fn get_sprite_handle_macc(&self) -> Handle<Image> {
         self.macc_image_handle.clone()
     }
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MapEdge {
+    Top,
+    Right,
+    Bottom,
+    Left
+}
+
+impl MapEdge {
+    pub fn opposite(&self) -> Self {
+        match self {
+            MapEdge::Top => MapEdge::Bottom,
+            MapEdge::Right => MapEdge::Left,
+            MapEdge::Bottom => MapEdge::Top,
+            MapEdge::Left => MapEdge::Right,
+        }
+    }
+
+    // Map edge a world space angle in degrees points at
+    pub fn from_edge_angle(angle: f32) -> Self {
+        match angle.rem_euclid(360.0) {
+            ang if (45.0..135.0).contains(&ang) => MapEdge::Top,
+            ang if (135.0..225.0).contains(&ang) => MapEdge::Left,
+            ang if (225.0..315.0).contains(&ang) => MapEdge::Bottom,
+            _ => MapEdge::Right
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum BoundaryKind {
+    // Nanites leaving the map are destroyed
+    Absorbing,
+    // Nanites can't leave the map
+    Reflective,
+    // The map wraps around to the opposite edge
+    Periodic,
+    // Nanites leaving the map go into the NaniteReserve and are blown back in
+    #[default]
+    Reservoir
+}
+
+impl BoundaryKind {
+    pub fn cycle(&self) -> Self {
+        match self {
+            BoundaryKind::Reservoir => BoundaryKind::Absorbing,
+            BoundaryKind::Absorbing => BoundaryKind::Reflective,
+            BoundaryKind::Reflective => BoundaryKind::Periodic,
+            BoundaryKind::Periodic => BoundaryKind::Reservoir,
+        }
+    }
+}
+
+impl Display for BoundaryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoundaryKind::Absorbing => write!(f, "Absorbing"),
+            BoundaryKind::Reflective => write!(f, "Reflective"),
+            BoundaryKind::Periodic => write!(f, "Periodic"),
+            BoundaryKind::Reservoir => write!(f, "Reservoir"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoundaryConditions {
+    pub top: BoundaryKind,
+    pub right: BoundaryKind,
+    pub bottom: BoundaryKind,
+    pub left: BoundaryKind
+}
+
+impl BoundaryConditions {
+    pub fn kind(&self, edge: MapEdge) -> BoundaryKind {
+        match edge {
+            MapEdge::Top => self.top,
+            MapEdge::Right => self.right,
+            MapEdge::Bottom => self.bottom,
+            MapEdge::Left => self.left,
+        }
+    }
+
+    pub fn set_kind(&mut self, edge: MapEdge, kind: BoundaryKind) {
+        match edge {
+            MapEdge::Top => self.top = kind,
+            MapEdge::Right => self.right = kind,
+            MapEdge::Bottom => self.bottom = kind,
+            MapEdge::Left => self.left = kind,
+        }
+    }
+
+    // Periodic edges only make sense in pairs so the opposite edge follows along
+    pub fn cycle(&mut self, edge: MapEdge) {
+        let was_periodic = self.kind(edge) == BoundaryKind::Periodic;
+        let kind = self.kind(edge).cycle();
+        self.set_kind(edge, kind);
+        if kind == BoundaryKind::Periodic || was_periodic {
+            self.set_kind(edge.opposite(), kind);
+        }
+    }
+
+    pub fn wraps_rows(&self) -> bool {
+        self.top == BoundaryKind::Periodic || self.bottom == BoundaryKind::Periodic
+    }
+
+    pub fn wraps_cols(&self) -> bool {
+        self.left == BoundaryKind::Periodic || self.right == BoundaryKind::Periodic
+    }
+}
@@ -0,0 +1,26 @@
+use bevy::ecs::system::Resource;
+
+#[derive(Resource)]
+pub struct BuildFunds {
+    pub amount: f32,
+    pub income: f32
+}
+
+impl Default for BuildFunds {
+    fn default() -> Self {
+        Self {
+            amount: 500.0,
+            income: 5.0
+        }
+    }
+}
+
+impl BuildFunds {
+    pub fn try_spend(&mut self, cost: f32) -> bool {
+        if self.amount < cost {
+            return false;
+        }
+        self.amount -= cost;
+        true
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::ecs::system::Resource;
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum DispersionMode {
+    // Full hexes spill their overflow onto every lower neighbor
+    #[default]
+    Overflow,
+    // Explicit finite volume diffusion between every pair of neighbors
+    Diffusion
+}
+
+#[derive(Resource)]
+pub struct DispersionSettings {
+    pub mode: DispersionMode,
+    // Fraction of the difference between two neighbors exchanged per unit of time
+    pub coefficient: f32,
+    // Simulated time covered by one dispersion step
+    pub timestep: f32
+}
+
+impl Default for DispersionSettings {
+    fn default() -> Self {
+        Self {
+            mode: DispersionMode::default(),
+            coefficient: 0.1,
+            timestep: 1.0
+        }
+    }
+}
+
+impl DispersionSettings {
+    // Explicit diffusion on a hex grid stays stable while coefficient * dt <= 1 / neighbors
+    pub const STABILITY_LIMIT: f32 = 1.0 / 6.0;
+    // Each step over the stability limit adds a sub step, past this a tick takes too long
+    pub const MAX_COEFFICIENT: f32 = 10.0;
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            DispersionMode::Overflow => DispersionMode::Diffusion,
+            DispersionMode::Diffusion => DispersionMode::Overflow,
+        }
+    }
+
+    // Number of sub steps needed to keep every step under the stability limit
+    pub fn substeps(&self) -> u32 {
+        ((self.coefficient * self.timestep / DispersionSettings::STABILITY_LIMIT).ceil() as u32).max(1)
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::ecs::system::Resource;
+
+use crate::components::{nanite::Nanite, hex_resource::HexResource, terrain::Terrain};
+
+pub const CHUNK_SIZE: usize = 32;
+
+// Per hex state of a CHUNK_SIZE x CHUNK_SIZE block of the map as dense arrays,
+// indexed by local_row * CHUNK_SIZE + local_col. Holes have no terrain
+pub struct HexChunk {
+    pub terrain: Vec<Option<Terrain>>,
+    pub nanites: Vec<Nanite>,
+    pub resources: Vec<HexResource>
+}
+
+impl HexChunk {
+    fn new() -> Self {
+        Self {
+            terrain: vec![None; CHUNK_SIZE * CHUNK_SIZE],
+            nanites: (0..CHUNK_SIZE * CHUNK_SIZE).map(|_| Nanite::new_empty()).collect(),
+            resources: (0..CHUNK_SIZE * CHUNK_SIZE).map(|_| HexResource::default()).collect()
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct NaniteField {
+    // Chunks in row major order
+    pub chunks: Vec<HexChunk>,
+    // Rows and columns of chunks
+    pub chunk_dims: (usize, usize),
+    // Rows and columns of hexes, see HexGrid::dimensions
+    pub dimensions: (usize, usize)
+}
+
+impl NaniteField {
+    pub fn new(dimensions: (usize, usize), cells: impl IntoIterator<Item = ((usize, usize), Terrain)>) -> Self {
+        let chunk_dims = (dimensions.0.div_ceil(CHUNK_SIZE), dimensions.1.div_ceil(CHUNK_SIZE));
+        let mut field = Self {
+            chunks: (0..chunk_dims.0 * chunk_dims.1).map(|_| HexChunk::new()).collect(),
+            chunk_dims,
+            dimensions
+        };
+        for (pos, terrain) in cells {
+            if let Some((chunk, cell)) = field.index(pos) {
+                let chunk = &mut field.chunks[chunk];
+                chunk.resources[cell] = HexResource::from_terrain(&terrain);
+                chunk.terrain[cell] = Some(terrain);
+            }
+        }
+        field
+    }
+
+    // Chunk and cell index of a position
+    fn index(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        if pos.0 >= self.dimensions.0 || pos.1 >= self.dimensions.1 {
+            return None;
+        }
+        let (chunk_row, chunk_col) = NaniteField::chunk_of(pos);
+        let cell = (pos.0 % CHUNK_SIZE) * CHUNK_SIZE + pos.1 % CHUNK_SIZE;
+        Some((chunk_row * self.chunk_dims.1 + chunk_col, cell))
+    }
+
+    pub fn chunk_of(pos: (usize, usize)) -> (usize, usize) {
+        (pos.0 / CHUNK_SIZE, pos.1 / CHUNK_SIZE)
+    }
+
+    pub fn terrain(&self, pos: (usize, usize)) -> Option<&Terrain> {
+        let (chunk, cell) = self.index(pos)?;
+        self.chunks[chunk].terrain[cell].as_ref()
+    }
+
+    pub fn nanite(&self, pos: (usize, usize)) -> Option<&Nanite> {
+        let (chunk, cell) = self.index(pos)?;
+        self.chunks[chunk].terrain[cell].as_ref()?;
+        Some(&self.chunks[chunk].nanites[cell])
+    }
+
+    pub fn nanite_mut(&mut self, pos: (usize, usize)) -> Option<&mut Nanite> {
+        let (chunk, cell) = self.index(pos)?;
+        self.chunks[chunk].terrain[cell].as_ref()?;
+        Some(&mut self.chunks[chunk].nanites[cell])
+    }
+
+    pub fn resource(&self, pos: (usize, usize)) -> Option<&HexResource> {
+        let (chunk, cell) = self.index(pos)?;
+        self.chunks[chunk].terrain[cell].as_ref()?;
+        Some(&self.chunks[chunk].resources[cell])
+    }
+
+    // Positions in a chunk that are part of the map
+    pub fn chunk_positions(&self, chunk: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (chunk.0 * CHUNK_SIZE..(chunk.0 + 1) * CHUNK_SIZE)
+            .flat_map(move |row| (chunk.1 * CHUNK_SIZE..(chunk.1 + 1) * CHUNK_SIZE).map(move |col| (row, col)))
+            .filter(|pos| self.terrain(*pos).is_some())
+    }
+
+    // Every hex that is part of the map
+    pub fn cells_mut(&mut self) -> impl Iterator<Item = (&Terrain, &mut Nanite, &mut HexResource)> {
+        self.chunks.iter_mut().flat_map(|chunk| {
+            chunk.terrain.iter()
+                .zip(chunk.nanites.iter_mut())
+                .zip(chunk.resources.iter_mut())
+                .filter_map(|((terrain, nanite), resource)| terrain.as_ref().map(|terrain| (terrain, nanite, resource)))
+        })
+    }
+
+    // Copy of every hex's nanites in row major order, holes are empty
+    pub fn nanite_snapshot(&self) -> Vec<Nanite> {
+        (0..self.dimensions.0)
+            .flat_map(|row| (0..self.dimensions.1).map(move |col| (row, col)))
+            .map(|pos| self.nanite(pos).cloned().unwrap_or_else(Nanite::new_empty))
+            .collect()
+    }
+
+    // Nanite totals in row major order, see HexGrid::index. Holes are empty
+    pub fn snapshot(&self) -> Vec<f32> {
+        (0..self.dimensions.0)
+            .flat_map(|row| (0..self.dimensions.1).map(move |col| (row, col)))
+            .map(|pos| self.nanite(pos).map_or(0.0, |nanite| nanite.nanite_total))
+            .collect()
+    }
+}
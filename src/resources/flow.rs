@@ -0,0 +1,67 @@
+use bevy::ecs::system::Resource;
+
+use super::hex::HexDirection;
+
+// Nanites carried out of a hex over one of its edges, off the map where there's no neighbor
+#[derive(Clone, Copy, Debug)]
+pub struct EdgeFlow {
+    pub pos: (usize, usize),
+    pub direction: HexDirection,
+    pub amount: f32
+}
+
+// Nanites each hex gained from its neighbors on the last tick, negative when it lost them.
+// Row major, see HexGrid::index. Empty until the first tick
+#[derive(Resource, Default)]
+pub struct NaniteFlow {
+    pub wind: Vec<f32>,
+    pub dispersion: Vec<f32>,
+    // What crossed each edge, once per edge from the side it left
+    pub wind_edges: Vec<EdgeFlow>,
+    pub dispersion_edges: Vec<EdgeFlow>
+}
+
+impl NaniteFlow {
+    pub fn net(&self, index: usize) -> f32 {
+        self.wind.get(index).unwrap_or(&0.0) + self.dispersion.get(index).unwrap_or(&0.0)
+    }
+
+    // Net flow of every hex in row major order
+    pub fn snapshot(&self, hexes: usize) -> Vec<f32> {
+        (0..hexes).map(|index| self.net(index)).collect()
+    }
+}
+
+// Arrows over the map for the last tick's edge flows, drawn over whichever MapState is shown
+#[derive(Resource)]
+pub struct FlowOverlay {
+    pub enabled: bool,
+    pub wind: bool,
+    pub dispersion: bool,
+    // Flow drawn at full thickness
+    pub max_amount: f32
+}
+
+impl Default for FlowOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wind: true,
+            dispersion: true,
+            max_amount: 5.0
+        }
+    }
+}
+
+impl FlowOverlay {
+    // Most parallel strokes an arrow is drawn with
+    pub const MAX_THICKNESS: usize = 5;
+    // Smallest flow drawn
+    pub const MIN_AMOUNT: f32 = 0.01;
+
+    // Strokes for a flow, at least one for anything over MIN_AMOUNT
+    pub fn thickness(&self, amount: f32) -> usize {
+        let fraction = (amount / self.max_amount).clamp(0.0, 1.0);
+        1 + (fraction * (FlowOverlay::MAX_THICKNESS - 1) as f32).round() as usize
+    }
+}
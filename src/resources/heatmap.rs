@@ -0,0 +1,190 @@
+use std::fmt::Display;
+use bevy::{ecs::system::Resource, render::color::Color};
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ColorRamp {
+    #[default]
+    Viridis,
+    Magma,
+    // Blue under half the range, red over it
+    Diverging,
+    // Readable with the common kinds of color blindness
+    Cividis
+}
+
+impl ColorRamp {
+    pub const ALL: [ColorRamp; 4] = [
+        ColorRamp::Viridis,
+        ColorRamp::Magma,
+        ColorRamp::Diverging,
+        ColorRamp::Cividis
+    ];
+
+    // Evenly spaced sRGB stops from low to high
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            ColorRamp::Viridis => &[
+                [68, 1, 84], [72, 40, 120], [62, 74, 137], [49, 104, 142], [38, 130, 142],
+                [31, 158, 137], [53, 183, 121], [109, 205, 89], [180, 222, 44], [253, 231, 37]
+            ],
+            ColorRamp::Magma => &[
+                [0, 0, 4], [24, 15, 61], [68, 15, 118], [114, 31, 129], [158, 47, 127],
+                [205, 64, 113], [241, 96, 93], [253, 150, 104], [254, 201, 141], [252, 253, 191]
+            ],
+            ColorRamp::Diverging => &[
+                [33, 102, 172], [103, 169, 207], [209, 229, 240], [247, 247, 247],
+                [253, 219, 199], [239, 138, 98], [178, 24, 43]
+            ],
+            ColorRamp::Cividis => &[
+                [0, 32, 77], [0, 51, 111], [57, 72, 107], [87, 92, 109], [112, 113, 115],
+                [138, 135, 121], [166, 157, 117], [196, 181, 108], [228, 207, 91], [255, 234, 70]
+            ],
+        }
+    }
+
+    // Color at t in 0..1, blended between the nearest stops
+    pub fn sample(&self, t: f32) -> Color {
+        let stops = self.stops();
+        let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let low = (scaled.floor() as usize).min(stops.len() - 2);
+        let blend = scaled - low as f32;
+        let channel = |i: usize| (stops[low][i] as f32 * (1.0 - blend) + stops[low + 1][i] as f32 * blend) / 255.0;
+        Color::rgb(channel(0), channel(1), channel(2))
+    }
+
+    pub fn next(&self) -> Self {
+        let index = ColorRamp::ALL.iter().position(|ramp| ramp == self).unwrap_or(0);
+        ColorRamp::ALL[(index + 1) % ColorRamp::ALL.len()]
+    }
+}
+
+impl Display for ColorRamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorRamp::Viridis => write!(f, "Viridis"),
+            ColorRamp::Magma => write!(f, "Magma"),
+            ColorRamp::Diverging => write!(f, "Diverging"),
+            ColorRamp::Cividis => write!(f, "Cividis"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum HeatmapScale {
+    #[default]
+    Linear,
+    // Spreads out the low end, ln(1 + value)
+    Log
+}
+
+impl Display for HeatmapScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeatmapScale::Linear => write!(f, "Linear"),
+            HeatmapScale::Log => write!(f, "Log"),
+        }
+    }
+}
+
+// How nanite totals are colored on the nanite map view
+#[derive(Resource)]
+pub struct HeatmapSettings {
+    pub ramp: ColorRamp,
+    pub scale: HeatmapScale,
+    // Nanite total at the top of the ramp, twice a hex's capacity puts full hexes in the middle
+    pub max_value: f32,
+    // Numbers on each hex once zoomed in past VALUE_ZOOM
+    pub show_values: bool
+}
+
+impl Default for HeatmapSettings {
+    fn default() -> Self {
+        Self {
+            ramp: ColorRamp::default(),
+            scale: HeatmapScale::default(),
+            max_value: 40.0,
+            show_values: false
+        }
+    }
+}
+
+impl HeatmapSettings {
+    // Largest camera scale the hex values are drawn at
+    pub const VALUE_ZOOM: f32 = 1.0;
+    // Nanites per tick at either end of the flux and rate of change overlays
+    pub const RATE_RANGE: f32 = 5.0;
+    // Ticks to fill at the bottom of the saturation overlay
+    pub const SATURATION_HORIZON: f32 = 600.0;
+
+    pub fn toggle_scale(&mut self) {
+        self.scale = match self.scale {
+            HeatmapScale::Linear => HeatmapScale::Log,
+            HeatmapScale::Log => HeatmapScale::Linear,
+        }
+    }
+
+    // Position of a value on the ramp
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, self.max_value);
+        match self.scale {
+            HeatmapScale::Linear => value / self.max_value,
+            HeatmapScale::Log => value.ln_1p() / self.max_value.ln_1p(),
+        }
+    }
+
+    // Value at a position on the ramp, the inverse of normalize
+    pub fn value_at(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self.scale {
+            HeatmapScale::Linear => t * self.max_value,
+            HeatmapScale::Log => (t * self.max_value.ln_1p()).exp_m1(),
+        }
+    }
+
+    pub fn color(&self, value: f32) -> Color {
+        self.ramp.sample(self.normalize(value))
+    }
+
+    // Gains above the middle of the diverging ramp and losses below it,
+    // range is the change at either end
+    pub fn signed_color(&self, value: f32, range: f32) -> Color {
+        let magnitude = value.abs().min(range);
+        let t = match self.scale {
+            HeatmapScale::Linear => magnitude / range,
+            HeatmapScale::Log => magnitude.ln_1p() / range.ln_1p(),
+        };
+        ColorRamp::Diverging.sample(0.5 + t.copysign(value) / 2.0)
+    }
+
+    // Hexes about to fill sit at the top of the ramp, ones that aren't filling are gray
+    pub fn saturation_color(&self, ticks: Option<f32>) -> Color {
+        match ticks {
+            Some(ticks) => self.ramp.sample(1.0 - ticks.clamp(0.0, HeatmapSettings::SATURATION_HORIZON).ln_1p() / HeatmapSettings::SATURATION_HORIZON.ln_1p()),
+            None => Color::DARK_GRAY
+        }
+    }
+
+    // Terrain tinted towards the heatmap color the fuller the hex is
+    pub fn blend_color(&self, terrain: Color, value: f32) -> Color {
+        mix(terrain, self.color(value), self.normalize(value))
+    }
+}
+
+// Green while a hex is mostly empty, through yellow to red once it's full
+pub fn danger_color(fill: f32) -> Color {
+    let fill = fill.clamp(0.0, 1.0);
+    if fill < 0.5 {
+        mix(Color::rgb(0.1, 0.6, 0.2), Color::rgb(0.95, 0.85, 0.1), fill * 2.0)
+    } else {
+        mix(Color::rgb(0.95, 0.85, 0.1), Color::rgb(0.85, 0.1, 0.1), fill * 2.0 - 1.0)
+    }
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    Color::rgb(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t
+    )
+}
@@ -1,65 +1,367 @@
-use bevy::ecs::{system::Resource, entity::Entity};
-use rand::{thread_rng, Rng};
+use std::{collections::HashMap, fmt::Display};
+use bevy::{ecs::system::Resource, math::{Vec2, Rect}, render::color::Color};
+use rand::Rng;
+use serde::{Serialize, Deserialize};
 
 use crate::components::grid_pos::GridPos;
 
-#[derive(Resource, Default, Debug, PartialEq)]
+use super::{boundary::{BoundaryConditions, BoundaryKind, MapEdge}, layout::{HexLayout, HexOrientation}};
+
+// Overlay shown on the map
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
 pub enum MapState {
     #[default]
     Terrain, 
-    Nanite
+    Nanite,
+    // Net nanites moved in or out by wind and dispersion on the last tick
+    Flux,
+    // Change in the nanite total since the tick before
+    RateOfChange,
+    // Terrain with an arrow per hex showing where the wind carries nanites
+    Wind,
+    // Ticks until a hex fills at its current rate
+    Saturation,
+    // How much a MACC standing on the hex is exposed to
+    Danger,
+    // Terrain tinted by the nanite heatmap
+    Blend
+}
+
+impl MapState {
+    pub const ALL: [MapState; 8] = [
+        MapState::Terrain,
+        MapState::Nanite,
+        MapState::Flux,
+        MapState::RateOfChange,
+        MapState::Wind,
+        MapState::Saturation,
+        MapState::Danger,
+        MapState::Blend
+    ];
+
+    pub fn next(&self) -> Self {
+        let index = MapState::ALL.iter().position(|state| state == self).unwrap_or(0);
+        MapState::ALL[(index + 1) % MapState::ALL.len()]
+    }
+
+    pub fn previous(&self) -> Self {
+        let index = MapState::ALL.iter().position(|state| state == self).unwrap_or(0);
+        MapState::ALL[(index + MapState::ALL.len() - 1) % MapState::ALL.len()]
+    }
+
+    // Alt and this number picks the overlay, shown on the map button bar
+    pub fn shortcut(&self) -> usize {
+        MapState::ALL.iter().position(|state| state == self).unwrap_or(0) + 1
+    }
+
+    // Overlays colored from nanite totals, which the heatmap legend describes
+    pub fn uses_heatmap(&self) -> bool {
+        matches!(self, MapState::Nanite | MapState::Blend)
+    }
+
+    // Selection and order markers, picked to stand out against this overlay's colors
+    pub fn highlight_color(&self) -> Color {
+        match self {
+            MapState::Flux | MapState::RateOfChange => Color::BLACK,
+            MapState::Danger => Color::CYAN,
+            _ => Color::WHITE,
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+impl Display for MapState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapState::Terrain => write!(f, "Terrain"),
+            MapState::Nanite => write!(f, "Nanite"),
+            MapState::Flux => write!(f, "Flux"),
+            MapState::RateOfChange => write!(f, "Rate of Change"),
+            MapState::Wind => write!(f, "Wind"),
+            MapState::Saturation => write!(f, "Time to Saturation"),
+            MapState::Danger => write!(f, "Danger"),
+            MapState::Blend => write!(f, "Terrain + Nanite"),
+        }
+    }
+}
+
+// Pointy layouts use the Left/Right edges and flat layouts the Top/Bottom ones,
+// see HexLayout::directions
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum HexDirection {
+    Top,
     TopLeft,
     TopRight,
     Right,
     BottomRight,
+    Bottom,
     BottomLeft,
     Left
 }
 
 impl HexDirection {
-    pub fn from_angle(angle: f32) -> Self {
-        match angle % 360.0 {
-            ang if (0.0..30.0).contains(&ang) => HexDirection::Right,
-            ang if (330.0..=360.0).contains(&ang) => HexDirection::Right,
-            ang if (30.0..90.0).contains(&ang) => HexDirection::BottomRight,
-            ang if (90.0..150.0).contains(&ang) => HexDirection::BottomLeft,
-            ang if (150.0..210.0).contains(&ang) => HexDirection::Left,
-            ang if (210.0..270.0).contains(&ang) => HexDirection::TopLeft,
-            ang if (270.0..330.0).contains(&ang) => HexDirection::TopRight,
-            _ => HexDirection::Right
+    pub fn opposite(&self) -> Self {
+        match self {
+            HexDirection::Top => HexDirection::Bottom,
+            HexDirection::TopLeft => HexDirection::BottomRight,
+            HexDirection::TopRight => HexDirection::BottomLeft,
+            HexDirection::Right => HexDirection::Left,
+            HexDirection::BottomRight => HexDirection::TopLeft,
+            HexDirection::Bottom => HexDirection::Top,
+            HexDirection::BottomLeft => HexDirection::TopRight,
+            HexDirection::Left => HexDirection::Right,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum EdgeAttribute {
+    #[default]
+    Open,
+    Wall,
+    // Fraction of the nanites let through the edge
+    Filter(f32)
+}
+
+impl EdgeAttribute {
+    // Filters let through 25%, 50% or 75%, see step_filter
+    pub const FILTER_STEP: f32 = 0.25;
+
+    pub fn permeability(&self) -> f32 {
+        match self {
+            EdgeAttribute::Open => 1.0,
+            EdgeAttribute::Wall => 0.0,
+            EdgeAttribute::Filter(permeability) => permeability.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn cycle(&self) -> Self {
+        match self {
+            EdgeAttribute::Open => EdgeAttribute::Wall,
+            EdgeAttribute::Wall => EdgeAttribute::Filter(0.5),
+            EdgeAttribute::Filter(_) => EdgeAttribute::Open,
+        }
+    }
+
+    // Lets more (positive steps) or less through a filter, other edges stay as they are
+    pub fn step_filter(&self, steps: i32) -> Self {
+        match self {
+            EdgeAttribute::Filter(permeability) => EdgeAttribute::Filter(
+                (permeability + steps as f32 * EdgeAttribute::FILTER_STEP).clamp(EdgeAttribute::FILTER_STEP, 1.0 - EdgeAttribute::FILTER_STEP)
+            ),
+            _ => *self
+        }
+    }
+}
+
+impl Display for EdgeAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeAttribute::Open => write!(f, "Open"),
+            EdgeAttribute::Wall => write!(f, "Wall"),
+            EdgeAttribute::Filter(permeability) => write!(f, "Filter {:.0}%", permeability * 100.0),
         }
     }
 }
 
+// Edges are keyed from the hex on their bottom/left side so each is stored once
+pub type EdgeKey = ((i32, i32), HexDirection);
+
 #[derive(Resource)]
 pub struct HexGrid {
-    pub grid: Vec<Vec<Entity>>,
-    pub selected_pos: Option<(usize, usize)>
+    // Whether each position is part of the map, in row major order, see HexGrid::index
+    pub cells: Vec<bool>,
+    // Rows and columns spanned by the cells
+    pub dimensions: (usize, usize),
+    pub layout: HexLayout,
+    pub selected_pos: Option<(usize, usize)>,
+    pub edges: HashMap<EdgeKey, EdgeAttribute>,
+    pub boundaries: BoundaryConditions
 }
 
 impl HexGrid {
 
     pub const HEX_RADIUS: f32 = 50.0;
 
-    //Even Masks
-    const MASK_EVEN_TOP_LEFT: (i32, i32) = (1, 0);
-    const MASK_EVEN_TOP_RIGHT: (i32, i32) = (1, 1);
-    const MASK_EVEN_RIGHT: (i32, i32) = (0, 1);
-    const MASK_EVEN_BOTTOM_RIGHT: (i32, i32) = (-1, 1);
-    const MASK_EVEN_BOTTOM_LEFT: (i32, i32) = (-1, 0);
-    const MASK_EVEN_LEFT: (i32, i32) = (0, -1);
-
-    //Odd Masks
-    const MASK_ODD_TOP_LEFT: (i32, i32) = (1, -1);
-    const MASK_ODD_TOP_RIGHT: (i32, i32) = (1, 0);
-    const MASK_ODD_RIGHT: (i32, i32) = (0, 1);
-    const MASK_ODD_BOTTOM_RIGHT: (i32, i32) = (-1, 0);
-    const MASK_ODD_BOTTOM_LEFT: (i32, i32) = (-1, -1);
-    const MASK_ODD_LEFT: (i32, i32) = (0, -1);
+    pub fn new(positions: &[(usize, usize)], layout: HexLayout) -> Self {
+        let dimensions = positions.iter().fold((0, 0), |(rows, cols), (row, col)| {
+            (rows.max(row + 1), cols.max(col + 1))
+        });
+        let mut cells = vec![false; dimensions.0 * dimensions.1];
+        for (row, col) in positions {
+            cells[row * dimensions.1 + col] = true;
+        }
+        Self {
+            cells,
+            dimensions,
+            layout,
+            selected_pos: None,
+            edges: HashMap::new(),
+            boundaries: BoundaryConditions::default()
+        }
+    }
+
+    // Positions that are part of the map in row major order
+    pub fn hexes(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let cols = self.dimensions.1;
+        self.cells.iter().enumerate()
+            .filter(|(_, present)| **present)
+            .map(move |(index, _)| (index / cols, index % cols))
+    }
+
+    // Row major index of a position, shared by the per hex arrays
+    pub fn index(&self, pos: (usize, usize)) -> usize {
+        pos.0 * self.dimensions.1 + pos.1
+    }
+
+    pub fn get(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        if pos.0 < self.dimensions.0 && pos.1 < self.dimensions.1 && self.cells[self.index(pos)] {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    // Hex containing a world space point, if it is part of the map
+    pub fn get_at_world(&self, point: Vec2) -> Option<(usize, usize)> {
+        let (row, col) = self.layout.world_to_pos(point);
+        match (usize::try_from(row), usize::try_from(col)) {
+            (Ok(row), Ok(col)) => self.get((row, col)),
+            _ => None
+        }
+    }
+
+    // Edge of the hex under a world point that the point is closest to
+    pub fn edge_at_world(&self, point: Vec2) -> Option<((i32, i32), HexDirection)> {
+        let pos = self.get_at_world(point)?;
+        let pos = (pos.0 as i32, pos.1 as i32);
+        let offset = point - self.layout.pos_to_world(pos);
+        Some((pos, self.layout.direction_from_edge_angle(offset.y.atan2(offset.x).to_degrees())))
+    }
+
+    // World space area covered by the map, offset rows and columns included
+    pub fn world_bounds(&self) -> Rect {
+        let (last_row, last_col) = (self.rows() - 1, self.cols() - 1);
+        [0, 1.min(last_row), (last_row - 1).max(0), last_row].into_iter()
+            .flat_map(|row| [0, 1.min(last_col), (last_col - 1).max(0), last_col].map(|col| (row, col)))
+            .map(|pos| Rect::from_center_size(self.layout.pos_to_world(pos), Vec2::ZERO))
+            .reduce(|bounds, point| bounds.union(point))
+            .unwrap()
+            .inset(self.layout.radius)
+    }
+
+    // Hexes whose center lies in a world space rectangle
+    pub fn hexes_in_rect(&self, rect: Rect) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let corners = [rect.min, rect.max, Vec2::new(rect.min.x, rect.max.y), Vec2::new(rect.max.x, rect.min.y)]
+            .map(|corner| self.layout.world_to_pos(corner));
+        let rows = (corners.iter().map(|pos| pos.0).min().unwrap() - 1).max(0)..=(corners.iter().map(|pos| pos.0).max().unwrap() + 1).min(self.rows() - 1);
+        let cols = (corners.iter().map(|pos| pos.1).min().unwrap() - 1).max(0)..=(corners.iter().map(|pos| pos.1).max().unwrap() + 1).min(self.cols() - 1);
+        rows.flat_map(move |row| cols.clone().map(move |col| (row as usize, col as usize)))
+            .filter(move |pos| self.get(*pos).is_some())
+            .filter(move |pos| rect.contains(self.layout.pos_to_world((pos.0 as i32, pos.1 as i32))))
+    }
+
+    // Hexes within a hex distance of a position
+    pub fn hexes_within(&self, center: (usize, usize), radius: u32) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let center = (center.0 as i32, center.1 as i32);
+        let reach = radius as i32 + 1;
+        (center.0 - reach..=center.0 + reach)
+            .flat_map(move |row| (center.1 - reach..=center.1 + reach).map(move |col| (row, col)))
+            .filter(move |pos| self.layout.distance(center, *pos) <= radius)
+            .filter_map(|(row, col)| match (usize::try_from(row), usize::try_from(col)) {
+                (Ok(row), Ok(col)) => self.get((row, col)),
+                _ => None
+            })
+    }
+
+    pub fn edge_key(&self, pos: (i32, i32), direction: HexDirection) -> EdgeKey {
+        if self.layout.edge_angle(direction) < 180.0 {
+            (self.wrap_pos(pos), direction)
+        } else {
+            (self.wrap_pos(self.layout.offset_pos(pos, &direction)), direction.opposite())
+        }
+    }
+
+    pub fn get_edge(&self, pos: (i32, i32), direction: HexDirection) -> EdgeAttribute {
+        self.edges.get(&self.edge_key(pos, direction)).copied().unwrap_or_default()
+    }
+
+    pub fn set_edge(&mut self, pos: (i32, i32), direction: HexDirection, attribute: EdgeAttribute) {
+        let key = self.edge_key(pos, direction);
+        if attribute == EdgeAttribute::Open {
+            self.edges.remove(&key);
+        } else {
+            self.edges.insert(key, attribute);
+        }
+    }
+
+    pub fn edge_permeability(&self, grid_pos: &GridPos, direction: HexDirection) -> f32 {
+        self.get_edge(grid_pos.to_int(), direction).permeability()
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.dimensions.0 as i32
+    }
+
+    pub fn cols(&self) -> i32 {
+        self.dimensions.1 as i32
+    }
+
+    // Brings positions past a periodic map edge back onto the grid. Only pairs edges
+    // can_wrap allows, see cycle_boundary
+    pub fn wrap_pos(&self, pos: (i32, i32)) -> (i32, i32) {
+        let row = if self.boundaries.wraps_rows() { pos.0.rem_euclid(self.rows()) } else { pos.0 };
+        let col = if self.boundaries.wraps_cols() { pos.1.rem_euclid(self.cols()) } else { pos.1 };
+        (row, col)
+    }
+
+    // Offset rows (or columns on flat layouts) shift every other line, wrapping an odd
+    // count would join lines of the same parity and leave the neighbors one way
+    pub fn can_wrap(&self, edge: MapEdge) -> bool {
+        let count = match (edge, self.layout.orientation()) {
+            (MapEdge::Top | MapEdge::Bottom, HexOrientation::Pointy) => self.rows(),
+            (MapEdge::Left | MapEdge::Right, HexOrientation::Flat) => self.cols(),
+            _ => return true
+        };
+        count % 2 == 0
+    }
+
+    // Next boundary kind of an edge, passing over Periodic where it can't wrap
+    pub fn cycle_boundary(&mut self, edge: MapEdge) {
+        let next = self.boundaries.kind(edge).cycle();
+        if next == BoundaryKind::Periodic && !self.can_wrap(edge) {
+            self.boundaries.set_kind(edge, next.cycle());
+        } else {
+            self.boundaries.cycle(edge);
+        }
+    }
+
+    // Boundary crossed when leaving the hex in a direction, None if there is a neighbor.
+    // Only the way off the map takes the kind of the map edge the direction points at,
+    // holes and inlets inside it turn nanites back
+    pub fn crossed_boundary(&self, grid_pos: &GridPos, direction: HexDirection) -> Option<BoundaryKind> {
+        match self.get_offset(grid_pos.to_int(), direction) {
+            Some(_) => None,
+            None if self.leads_off_map(grid_pos.to_int(), direction) => Some(self.boundaries.kind(MapEdge::from_edge_angle(self.layout.edge_angle(direction)))),
+            None => Some(BoundaryKind::Reflective),
+        }
+    }
+
+    // Whether heading on from the hex in a direction leaves the grid without reaching another hex
+    fn leads_off_map(&self, pos: (i32, i32), direction: HexDirection) -> bool {
+        let mut pos = pos;
+        for _ in 0..self.rows() + self.cols() {
+            pos = self.wrap_pos(self.layout.offset_pos(pos, &direction));
+            match (usize::try_from(pos.0), usize::try_from(pos.1)) {
+                (Ok(row), Ok(col)) if row < self.dimensions.0 && col < self.dimensions.1 => {
+                    if self.get((row, col)).is_some() {
+                        return false;
+                    }
+                },
+                _ => return true
+            }
+        }
+        false
+    }
 
     pub fn select_pos(&mut self, pos: (usize, usize)) {
         self.selected_pos = Some(pos);
@@ -69,12 +371,8 @@ impl HexGrid {
         self.selected_pos = None;
     }
 
-    pub fn get_selected(&self) -> Option<Entity> {
-        let (row, col) = match self.selected_pos {
-            Some(p) => p,
-            None => return None,
-        };
-        self.grid.get(row)?.get(col).cloned()
+    pub fn get_selected(&self) -> Option<(usize, usize)> {
+        self.get(self.selected_pos?)
     }
 
     pub fn has_selected(&self) -> bool {
@@ -83,194 +381,47 @@ impl HexGrid {
 
     pub fn get_neigbors(&self, grid_pos: &GridPos) -> HexNeighbors {
         let pos = grid_pos.to_int();
-        if grid_pos.row_is_even() {
-            //Use even mask
-            HexNeighbors {
-                top_left: self.get_entity_offset(pos, HexGrid::MASK_EVEN_TOP_LEFT),
-                top_right: self.get_entity_offset(pos, HexGrid::MASK_EVEN_TOP_RIGHT),
-                left: self.get_entity_offset(pos, HexGrid::MASK_EVEN_LEFT),
-                right: self.get_entity_offset(pos, HexGrid::MASK_EVEN_RIGHT),
-                bottom_left: self.get_entity_offset(pos, HexGrid::MASK_EVEN_BOTTOM_LEFT),
-                bottom_right: self.get_entity_offset(pos, HexGrid::MASK_EVEN_BOTTOM_RIGHT),
-            }
-        } else {
-            //Use odd mask
-            HexNeighbors {
-                top_left: self.get_entity_offset(pos, HexGrid::MASK_ODD_TOP_LEFT),
-                top_right: self.get_entity_offset(pos, HexGrid::MASK_ODD_TOP_RIGHT),
-                left: self.get_entity_offset(pos, HexGrid::MASK_ODD_LEFT),
-                right: self.get_entity_offset(pos, HexGrid::MASK_ODD_RIGHT),
-                bottom_left: self.get_entity_offset(pos, HexGrid::MASK_ODD_BOTTOM_LEFT),
-                bottom_right: self.get_entity_offset(pos, HexGrid::MASK_ODD_BOTTOM_RIGHT),
-            }
+        HexNeighbors {
+            neighbors: self.layout.directions().map(|direction| (direction, self.get_offset(pos, direction)))
         }
     }
 
-    pub fn get_wind_neighors_new(&self, grid_pos: &GridPos, direction: f32) -> Option<Entity> {
-        let pos = grid_pos.to_int();
-        if grid_pos.row_is_even() {
-            match HexDirection::from_angle(direction) {
-                HexDirection::TopLeft => self.get_entity_offset(pos, HexGrid::MASK_EVEN_TOP_LEFT),
-                HexDirection::TopRight => self.get_entity_offset(pos, HexGrid::MASK_EVEN_TOP_RIGHT),
-                HexDirection::Right => self.get_entity_offset(pos, HexGrid::MASK_EVEN_RIGHT),
-                HexDirection::BottomRight => self.get_entity_offset(pos, HexGrid::MASK_EVEN_BOTTOM_RIGHT),
-                HexDirection::BottomLeft => self.get_entity_offset(pos, HexGrid::MASK_EVEN_BOTTOM_LEFT),
-                HexDirection::Left => self.get_entity_offset(pos, HexGrid::MASK_EVEN_LEFT),
-            }
-        } else {
-            match HexDirection::from_angle(direction) {
-                HexDirection::TopLeft => self.get_entity_offset(pos, HexGrid::MASK_ODD_TOP_LEFT),
-                HexDirection::TopRight => self.get_entity_offset(pos, HexGrid::MASK_ODD_TOP_RIGHT),
-                HexDirection::Right => self.get_entity_offset(pos, HexGrid::MASK_ODD_RIGHT),
-                HexDirection::BottomRight => self.get_entity_offset(pos, HexGrid::MASK_ODD_BOTTOM_RIGHT),
-                HexDirection::BottomLeft => self.get_entity_offset(pos, HexGrid::MASK_ODD_BOTTOM_LEFT),
-                HexDirection::Left => self.get_entity_offset(pos, HexGrid::MASK_ODD_LEFT),
-            }
-        }
+    pub fn get_wind_neighors_new(&self, grid_pos: &GridPos, direction: f32) -> Option<(usize, usize)> {
+        self.get_offset(grid_pos.to_int(), self.layout.direction_from_angle(direction))
     }
 
-    pub fn direction_edges(&self, angle: f32) -> Vec<Entity> {
-        return match angle % 360.0 {
-            // Full Right
-            ang if (337.5..360.0).contains(&ang) => {
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(row.len() - 1).unwrap().clone()
-                    );
-                }
-                rev_vec
-            },
-            // Full Right
-            ang if (0.0..22.5).contains(&ang) => {
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(row.len() - 1).unwrap().clone()
-                    );
-                }
-                rev_vec
-            },
-            // Bottom Right
-            ang if (22.5..67.5).contains(&ang) => {
-                let bottom = self.grid.first().unwrap().clone().into_iter();
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len() + bottom.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(row.len() - 1).unwrap().clone()
-                    );
-                }
-                for entity in bottom {
-                    rev_vec.push(entity)
-                }
-                rev_vec
-            },
-            // Full Bottom
-            ang if (67.5..112.5).contains(&ang) => {
-                self.grid.first().unwrap().clone()
-            },
-            // Bottom Left
-            ang if (112.5..157.5).contains(&ang) => {
-                let bottom = self.grid.first().unwrap().clone().into_iter();
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len() + bottom.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(0).unwrap().clone()
-                    );
-                }
-                for ent in bottom {
-                    rev_vec.push(ent)
-                }
-                rev_vec
-            },
-            // Full Left
-            ang if (157.5..202.5).contains(&ang) => {
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(0).unwrap().clone()
-                    );
-                }
-                rev_vec
-            },
-            // Top Left
-            ang if (202.5..247.5).contains(&ang) => {
-                let top = self.grid.last().unwrap().clone().into_iter();
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len() + top.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(0).unwrap().clone()
-                    );
-                }
-                for ent in top {
-                    rev_vec.push(ent)
-                }
-                rev_vec
-            },
-            // Full Top
-            ang if (247.5..292.5).contains(&ang) => {
-                self.grid.last().unwrap().clone()
-            },
-            // Top Right
-            ang if (292.5..337.5).contains(&ang) => {
-                let top = self.grid.last().unwrap().clone().into_iter();
-                let mut rev_vec: Vec<Entity> = Vec::with_capacity(self.grid.len() + top.len());
-                for i in 0..self.grid.len() {
-                    let row = self.grid.get(i).unwrap(); 
-                    rev_vec.push(
-                        row.get(row.len() - 1).unwrap().clone()
-                    );
-                }
-                for entity in top {
-                    rev_vec.push(entity)
-                }
-                rev_vec      
-            },
-            _ => {
-                eprintln!("angle not covered in match {}", &angle);
-                self.grid.get(0).unwrap().clone()
-            }
-        }
+    // Hexes missing a neighbor on the side an angle points towards, where that
+    // side of the map has the given boundary kind
+    pub fn direction_edges(&self, angle: f32, kind: BoundaryKind) -> Vec<(usize, usize)> {
+        let facing: Vec<HexDirection> = self.layout.facing(angle).collect();
+        self.hexes()
+            .filter(|pos| {
+                let grid_pos = GridPos { pos: *pos };
+                facing.iter().any(|direction| self.crossed_boundary(&grid_pos, *direction) == Some(kind))
+            })
+            .collect()
     }
 
-    fn get_entity_offset(&self, pos: (i32, i32), offset: (i32, i32)) -> Option<Entity> {
-        let (row, col) = match (usize::try_from(pos.0 + offset.0), usize::try_from(pos.1 + offset.1)) {
+    fn get_offset(&self, pos: (i32, i32), direction: HexDirection) -> Option<(usize, usize)> {
+        let (row, col) = self.wrap_pos(self.layout.offset_pos(pos, &direction));
+        let (row, col) = match (usize::try_from(row), usize::try_from(col)) {
             (Ok(r), Ok(c)) => (r, c),
             _ => return None
         };
-        self.grid.get(row)?.get(col).cloned()
+        self.get((row, col))
     }
 
 }
 
 #[derive(Debug)]
 pub struct HexNeighbors {
-    pub top_left: Option<Entity>,
-    pub top_right: Option<Entity>,
-    pub left: Option<Entity>,
-    pub right: Option<Entity>,
-    pub bottom_left: Option<Entity>,
-    pub bottom_right: Option<Entity>
+    pub neighbors: [(HexDirection, Option<(usize, usize)>); 6]
 }
 
 impl HexNeighbors {
-    pub fn some_neighbors(&self) -> impl Iterator<Item = Entity> {
-        [
-            self.top_left,
-            self.top_right,
-            self.left,
-            self.right,
-            self.bottom_left,
-            self.bottom_right
-        ].into_iter()
-        .filter_map(|option| option)
-        .into_iter()
+    pub fn some_neighbors(self) -> impl Iterator<Item = (HexDirection, (usize, usize))> {
+        self.neighbors.into_iter()
+            .filter_map(|(direction, option)| option.map(|pos| (direction, pos)))
     }
 }
 
@@ -284,10 +435,43 @@ impl NaniteReserve {
         self.amount += amount
     }
 
-    pub fn pull(&mut self) -> f32 {
-        let mut rng = thread_rng();
+    pub fn pull(&mut self, rng: &mut impl Rng) -> f32 {
         let amount = self.amount * rng.gen_range(0.0..1.0);
         self.amount -= amount;
         amount
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::layout::{HexOffset, test_fixtures::{OFFSETS, rectangle}};
+
+    #[test]
+    fn wrapped_neighbors_are_symmetric() {
+        for offset in OFFSETS {
+            for (rows, cols) in [(4, 6), (5, 6), (4, 7), (6, 6)] {
+                let mut hex_grid = rectangle(rows, cols, offset);
+                for edge in [MapEdge::Top, MapEdge::Left] {
+                    if hex_grid.can_wrap(edge) {
+                        hex_grid.boundaries.set_kind(edge, BoundaryKind::Periodic);
+                        hex_grid.boundaries.set_kind(edge.opposite(), BoundaryKind::Periodic);
+                    }
+                }
+                for pos in hex_grid.hexes() {
+                    for (direction, neighbor) in hex_grid.get_neigbors(&GridPos { pos }).some_neighbors() {
+                        let back = hex_grid.get_offset((neighbor.0 as i32, neighbor.1 as i32), direction.opposite());
+                        assert_eq!(back, Some(pos), "{offset:?} {rows}x{cols} {pos:?} {direction:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn odd_offset_lines_do_not_wrap() {
+        assert!(!rectangle(5, 6, HexOffset::OddR).can_wrap(MapEdge::Top));
+        assert!(rectangle(5, 6, HexOffset::OddR).can_wrap(MapEdge::Left));
+        assert!(!rectangle(6, 5, HexOffset::EvenQ).can_wrap(MapEdge::Right));
+        assert!(rectangle(6, 5, HexOffset::EvenQ).can_wrap(MapEdge::Bottom));
+    }
+}
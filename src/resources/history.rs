@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use bevy::ecs::system::Resource;
+
+use crate::components::nanite::Nanite;
+
+use super::weather::Weather;
+
+// State of the field after a tick
+pub struct HistoryEntry {
+    pub tick: u64,
+    // Rows and columns of the field, see NaniteField::dimensions
+    pub dimensions: (usize, usize),
+    // Row major, see NaniteField::nanite_snapshot
+    pub nanites: Vec<Nanite>,
+    // Net nanites moved in by wind and dispersion, see NaniteFlow
+    pub flow: Vec<f32>,
+    pub weather: Weather
+}
+
+impl HistoryEntry {
+    pub fn nanite(&self, pos: (usize, usize)) -> Option<&Nanite> {
+        if pos.0 >= self.dimensions.0 || pos.1 >= self.dimensions.1 {
+            return None;
+        }
+        self.nanites.get(pos.0 * self.dimensions.1 + pos.1)
+    }
+
+    pub fn flow(&self, pos: (usize, usize)) -> f32 {
+        if pos.0 >= self.dimensions.0 || pos.1 >= self.dimensions.1 {
+            return 0.0;
+        }
+        self.flow.get(pos.0 * self.dimensions.1 + pos.1).copied().unwrap_or(0.0)
+    }
+}
+
+// Ring buffer of the last ticks of the field, newest at the back
+#[derive(Resource)]
+pub struct FieldHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    // Tick shown on the map instead of the live field
+    viewing: Option<u64>
+}
+
+impl FieldHistory {
+    // Most ticks kept, bigger maps keep fewer to stay under MEMORY_BUDGET
+    pub const MAX_TICKS: usize = 600;
+    pub const MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+    // Entries hold the whole rows by columns field, holes and all
+    pub fn new(dimensions: (usize, usize)) -> Self {
+        let entry_size = (dimensions.0 * dimensions.1).max(1) * (std::mem::size_of::<Nanite>() + std::mem::size_of::<f32>());
+        Self {
+            entries: VecDeque::new(),
+            capacity: (FieldHistory::MEMORY_BUDGET / entry_size).clamp(1, FieldHistory::MAX_TICKS),
+            viewing: None
+        }
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        // After a restart the ticks come again from 0, what was kept from them is stale
+        while self.entries.back().is_some_and(|last| last.tick >= entry.tick) {
+            self.entries.pop_back();
+        }
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+
+        // Keep looking at the oldest tick rather than one that fell out
+        if let (Some(viewing), Some((first, _))) = (self.viewing, self.range()) {
+            if viewing < first {
+                self.viewing = Some(first);
+            }
+        }
+    }
+
+    // First and last tick kept
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((self.entries.front()?.tick, self.entries.back()?.tick))
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&HistoryEntry> {
+        let first = self.entries.front()?.tick;
+        self.entries.get(tick.checked_sub(first)? as usize)
+    }
+
+    pub fn view(&mut self, tick: u64) {
+        self.viewing = Some(tick);
+    }
+
+    pub fn view_live(&mut self) {
+        self.viewing = None;
+    }
+
+    pub fn viewing(&self) -> Option<u64> {
+        self.viewing
+    }
+
+    // Entry shown on the map, None while showing the live field
+    pub fn viewed(&self) -> Option<&HistoryEntry> {
+        self.get(self.viewing?)
+    }
+}
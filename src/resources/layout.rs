@@ -0,0 +1,327 @@
+use bevy::{math::Vec2, render::mesh::{Mesh, shape, VertexAttributeValues}};
+use serde::{Serialize, Deserialize};
+
+use super::hex::{HexGrid, HexDirection};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HexOrientation {
+    // Corner at the top, rows of hexes
+    #[default]
+    Pointy,
+    // Edge at the top, columns of hexes
+    Flat
+}
+
+// Which rows (pointy) or columns (flat) are pushed half a hex over
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HexOffset {
+    // Odd rows shifted right
+    OddR,
+    // Odd rows shifted left
+    #[default]
+    EvenR,
+    // Odd columns shifted up
+    OddQ,
+    // Odd columns shifted down
+    EvenQ
+}
+
+impl HexOffset {
+    pub fn orientation(&self) -> HexOrientation {
+        match self {
+            HexOffset::OddR | HexOffset::EvenR => HexOrientation::Pointy,
+            HexOffset::OddQ | HexOffset::EvenQ => HexOrientation::Flat,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "odd-r" => Some(HexOffset::OddR),
+            "even-r" => Some(HexOffset::EvenR),
+            "odd-q" => Some(HexOffset::OddQ),
+            "even-q" => Some(HexOffset::EvenQ),
+            _ => None
+        }
+    }
+}
+
+// Maps (row, col) offset positions to world space and between neighbors.
+// Rows go up in +y and columns go right in +x for every offset
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HexLayout {
+    pub offset: HexOffset,
+    pub radius: f32
+}
+
+impl Default for HexLayout {
+    fn default() -> Self {
+        Self {
+            offset: HexOffset::default(),
+            radius: HexGrid::HEX_RADIUS
+        }
+    }
+}
+
+impl HexLayout {
+    pub fn orientation(&self) -> HexOrientation {
+        self.offset.orientation()
+    }
+
+    // The six directions with an edge, counter clockwise
+    pub fn directions(&self) -> [HexDirection; 6] {
+        match self.orientation() {
+            HexOrientation::Pointy => [
+                HexDirection::Right,
+                HexDirection::TopRight,
+                HexDirection::TopLeft,
+                HexDirection::Left,
+                HexDirection::BottomLeft,
+                HexDirection::BottomRight
+            ],
+            HexOrientation::Flat => [
+                HexDirection::TopRight,
+                HexDirection::Top,
+                HexDirection::TopLeft,
+                HexDirection::BottomLeft,
+                HexDirection::Bottom,
+                HexDirection::BottomRight
+            ],
+        }
+    }
+
+    // Angle in degrees of the edge normal in world space, counter clockwise from +x
+    pub fn edge_angle(&self, direction: HexDirection) -> f32 {
+        let diagonal = match self.orientation() {
+            HexOrientation::Pointy => 60.0,
+            HexOrientation::Flat => 30.0,
+        };
+        match direction {
+            HexDirection::Right => 0.0,
+            HexDirection::TopRight => diagonal,
+            HexDirection::Top => 90.0,
+            HexDirection::TopLeft => 180.0 - diagonal,
+            HexDirection::Left => 180.0,
+            HexDirection::BottomLeft => 180.0 + diagonal,
+            HexDirection::Bottom => 270.0,
+            HexDirection::BottomRight => 360.0 - diagonal,
+        }
+    }
+
+    // Angle of the direction following the Weather::wind_direction convention
+    pub fn wind_angle(&self, direction: HexDirection) -> f32 {
+        (360.0 - self.edge_angle(direction)) % 360.0
+    }
+
+    // Direction closest to a Weather::wind_direction style angle
+    pub fn direction_from_angle(&self, angle: f32) -> HexDirection {
+        self.directions().into_iter()
+            .min_by(|a, b| angle_between(self.wind_angle(*a), angle).total_cmp(&angle_between(self.wind_angle(*b), angle)))
+            .unwrap()
+    }
+
+    // Every direction, closest to a Weather::wind_direction style angle first
+    pub fn directions_by_angle(&self, angle: f32) -> [HexDirection; 6] {
+        let mut directions = self.directions();
+        directions.sort_by(|a, b| angle_between(self.wind_angle(*a), angle).total_cmp(&angle_between(self.wind_angle(*b), angle)));
+        directions
+    }
+
+    // Edge whose normal is closest to a world space angle in degrees
+    pub fn direction_from_edge_angle(&self, angle: f32) -> HexDirection {
+        self.directions().into_iter()
+            .min_by(|a, b| angle_between(self.edge_angle(*a), angle).total_cmp(&angle_between(self.edge_angle(*b), angle)))
+            .unwrap()
+    }
+
+    // Directions within 60 degrees of a Weather::wind_direction style angle
+    pub fn facing(&self, angle: f32) -> impl Iterator<Item = HexDirection> + '_ {
+        self.directions().into_iter().filter(move |direction| angle_between(self.wind_angle(*direction), angle) < 60.0)
+    }
+
+    // Axial (q, r) coordinates of an offset position
+    pub fn pos_to_axial(&self, pos: (i32, i32)) -> (i32, i32) {
+        let (row, col) = pos;
+        match self.offset {
+            HexOffset::OddR => (col - (row - (row & 1)) / 2, row),
+            HexOffset::EvenR => (col - (row + (row & 1)) / 2, row),
+            HexOffset::OddQ => (col, row - (col - (col & 1)) / 2),
+            HexOffset::EvenQ => (col, row - (col + (col & 1)) / 2),
+        }
+    }
+
+    pub fn axial_to_pos(&self, axial: (i32, i32)) -> (i32, i32) {
+        let (q, r) = axial;
+        match self.offset {
+            HexOffset::OddR => (r, q + (r - (r & 1)) / 2),
+            HexOffset::EvenR => (r, q + (r + (r & 1)) / 2),
+            HexOffset::OddQ => (r + (q - (q & 1)) / 2, q),
+            HexOffset::EvenQ => (r + (q + (q & 1)) / 2, q),
+        }
+    }
+
+    // Axial step to the neighbor in a direction, directions the orientation
+    // doesn't have stay in place
+    fn axial_step(&self, direction: HexDirection) -> (i32, i32) {
+        match (self.orientation(), direction) {
+            (HexOrientation::Pointy, HexDirection::Right) => (1, 0),
+            (HexOrientation::Pointy, HexDirection::TopRight) => (0, 1),
+            (HexOrientation::Pointy, HexDirection::TopLeft) => (-1, 1),
+            (HexOrientation::Pointy, HexDirection::Left) => (-1, 0),
+            (HexOrientation::Pointy, HexDirection::BottomLeft) => (0, -1),
+            (HexOrientation::Pointy, HexDirection::BottomRight) => (1, -1),
+            (HexOrientation::Flat, HexDirection::TopRight) => (1, 0),
+            (HexOrientation::Flat, HexDirection::Top) => (0, 1),
+            (HexOrientation::Flat, HexDirection::TopLeft) => (-1, 1),
+            (HexOrientation::Flat, HexDirection::BottomLeft) => (-1, 0),
+            (HexOrientation::Flat, HexDirection::Bottom) => (0, -1),
+            (HexOrientation::Flat, HexDirection::BottomRight) => (1, -1),
+            _ => (0, 0)
+        }
+    }
+
+    pub fn offset_pos(&self, pos: (i32, i32), direction: &HexDirection) -> (i32, i32) {
+        let (q, r) = self.pos_to_axial(pos);
+        let (dq, dr) = self.axial_step(*direction);
+        self.axial_to_pos((q + dq, r + dr))
+    }
+
+    pub fn distance(&self, a: (i32, i32), b: (i32, i32)) -> u32 {
+        let (q1, r1) = self.pos_to_axial(a);
+        let (q2, r2) = self.pos_to_axial(b);
+        let (dq, dr) = (q1 - q2, r1 - r2);
+        ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as u32
+    }
+
+    pub fn pos_to_world(&self, pos: (i32, i32)) -> Vec2 {
+        let (q, r) = self.pos_to_axial(pos);
+        let (q, r) = (q as f32, r as f32);
+        let sqrt_3 = 3.0_f32.sqrt();
+        match self.orientation() {
+            HexOrientation::Pointy => Vec2::new(sqrt_3 * (q + r * 0.5), 1.5 * r) * self.radius,
+            HexOrientation::Flat => Vec2::new(1.5 * q, sqrt_3 * (r + q * 0.5)) * self.radius,
+        }
+    }
+
+    // Offset position of the hex containing a world space point
+    pub fn world_to_pos(&self, point: Vec2) -> (i32, i32) {
+        let point = point / self.radius;
+        let sqrt_3 = 3.0_f32.sqrt();
+        let (q, r) = match self.orientation() {
+            HexOrientation::Pointy => {
+                let r = point.y / 1.5;
+                (point.x / sqrt_3 - r * 0.5, r)
+            },
+            HexOrientation::Flat => {
+                let q = point.x / 1.5;
+                (q, point.y / sqrt_3 - q * 0.5)
+            },
+        };
+
+        // Round in cube coordinates, fixing up the component that moved the most
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        self.axial_to_pos((rq as i32, rr as i32))
+    }
+
+    // World space end points of a hex edge
+    pub fn edge_segment(&self, pos: (i32, i32), direction: &HexDirection) -> (Vec2, Vec2) {
+        let center = self.pos_to_world(pos);
+        let normal = self.edge_angle(*direction).to_radians();
+        (
+            center + Vec2::from_angle(normal - 30.0_f32.to_radians()) * self.radius,
+            center + Vec2::from_angle(normal + 30.0_f32.to_radians()) * self.radius
+        )
+    }
+
+    // Corner offsets from the hex center, counter clockwise
+    pub fn corners(&self, radius: f32) -> [Vec2; 6] {
+        self.directions().map(|direction| Vec2::from_angle((self.edge_angle(direction) + 30.0).to_radians()) * radius)
+    }
+
+    // Hexagon mesh turned to match the orientation
+    pub fn hex_mesh(&self, radius: f32) -> Mesh {
+        let mut mesh: Mesh = shape::RegularPolygon::new(radius, 6).into();
+        // RegularPolygon puts a corner at the top
+        if self.orientation() == HexOrientation::Flat {
+            let rotation = Vec2::from_angle(30.0_f32.to_radians());
+            if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+                for position in positions.iter_mut() {
+                    let rotated = rotation.rotate(Vec2::new(position[0], position[1]));
+                    position[0] = rotated.x;
+                    position[1] = rotated.y;
+                }
+            }
+        }
+        mesh
+    }
+}
+
+// Smallest difference between two angles in degrees
+fn angle_between(a: f32, b: f32) -> f32 {
+    let diff = (a - b).rem_euclid(360.0);
+    diff.min(360.0 - diff)
+}
+
+// Fixtures for the layout and hex grid tests
+#[cfg(test)]
+pub(crate) mod test_fixtures {
+    use super::*;
+
+    pub const OFFSETS: [HexOffset; 4] = [HexOffset::OddR, HexOffset::EvenR, HexOffset::OddQ, HexOffset::EvenQ];
+
+    // Full rows x cols map without holes
+    pub fn rectangle(rows: usize, cols: usize, offset: HexOffset) -> HexGrid {
+        let positions: Vec<(usize, usize)> = (0..rows).flat_map(|row| (0..cols).map(move |col| (row, col))).collect();
+        HexGrid::new(&positions, HexLayout { offset, ..HexLayout::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_fixtures::OFFSETS;
+
+    fn positions() -> impl Iterator<Item = (i32, i32)> {
+        (-5..6).flat_map(|row| (-5..6).map(move |col| (row, col)))
+    }
+
+    #[test]
+    fn axial_round_trip() {
+        for offset in OFFSETS {
+            let layout = HexLayout { offset, ..HexLayout::default() };
+            for pos in positions() {
+                assert_eq!(layout.axial_to_pos(layout.pos_to_axial(pos)), pos, "{offset:?} {pos:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn world_round_trip() {
+        for offset in OFFSETS {
+            let layout = HexLayout { offset, ..HexLayout::default() };
+            for pos in positions() {
+                assert_eq!(layout.world_to_pos(layout.pos_to_world(pos)), pos, "{offset:?} {pos:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn offset_pos_comes_back_the_opposite_way() {
+        for offset in OFFSETS {
+            let layout = HexLayout { offset, ..HexLayout::default() };
+            for pos in positions() {
+                for direction in layout.directions() {
+                    let neighbor = layout.offset_pos(pos, &direction);
+                    assert_eq!(layout.distance(pos, neighbor), 1, "{offset:?} {pos:?} {direction:?}");
+                    assert_eq!(layout.offset_pos(neighbor, &direction.opposite()), pos, "{offset:?} {pos:?} {direction:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use bevy::ecs::system::Resource;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::components::terrain::Terrain;
+
+use super::{layout::{HexLayout, HexOffset}, hex::HexGrid, field::NaniteField};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MapShape {
+    Rectangle { rows: usize, cols: usize },
+    Hexagon { radius: usize },
+    // Text rows from the top of the map down, '.' is land, '~' is water and anything else a hole
+    Ascii(String)
+}
+
+impl MapShape {
+    // Grid positions that make up the map, with their terrain if the shape defines it
+    pub fn cells(&self, layout: &HexLayout) -> Vec<((usize, usize), Option<Terrain>)> {
+        match self {
+            MapShape::Rectangle { rows, cols } => (0..*rows)
+                .flat_map(|row| (0..*cols).map(move |col| ((row, col), None)))
+                .collect(),
+            MapShape::Hexagon { radius } => {
+                let center = (*radius as i32, *radius as i32);
+                (0..=radius * 2)
+                    .flat_map(|row| (0..=radius * 2).map(move |col| (row, col)))
+                    .filter(|(row, col)| layout.distance(center, (*row as i32, *col as i32)) <= *radius as u32)
+                    .map(|pos| (pos, None))
+                    .collect()
+            },
+            MapShape::Ascii(text) => {
+                let lines: Vec<&str> = text.lines().collect();
+                lines.iter().rev().enumerate()
+                    .flat_map(|(row, line)| line.chars().enumerate().filter_map(move |(col, tile)| {
+                        match tile {
+                            '.' => Some(((row, col), Some(Terrain::Land))),
+                            '~' => Some(((row, col), Some(Terrain::Water))),
+                            _ => None
+                        }
+                    }))
+                    .collect()
+            },
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct MapConfig {
+    pub shape: MapShape,
+    pub layout: HexLayout
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            shape: MapShape::Rectangle { rows: 16, cols: 16 },
+            layout: HexLayout::default()
+        }
+    }
+}
+
+impl MapConfig {
+    // Grid and field of the map, terrain the shape leaves open is rolled from the rng
+    pub fn generate(&self, rng: &mut impl Rng) -> (HexGrid, NaniteField) {
+        let cells: Vec<((usize, usize), Terrain)> = self.shape.cells(&self.layout).into_iter()
+            .map(|(pos, terrain)| (pos, terrain.unwrap_or_else(|| Terrain::from_random(rng))))
+            .collect();
+        let positions: Vec<(usize, usize)> = cells.iter().map(|(pos, _)| *pos).collect();
+        let hex_grid = HexGrid::new(&positions, self.layout);
+        let nanite_field = NaniteField::new(hex_grid.dimensions, cells);
+        (hex_grid, nanite_field)
+    }
+
+    // Flags from_args reads, each followed by a value
+    pub const ARGS: [&'static str; 4] = ["--map", "--size", "--hexagon", "--layout"];
+
+    // Reads --map <file>, --size <rows>x<cols>, --hexagon <radius> and
+    // --layout <odd-r|even-r|odd-q|even-q> from the command line
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut map_config = MapConfig::default();
+        for pair in args.windows(2) {
+            match (pair[0].as_str(), pair[1].as_str()) {
+                ("--map", path) => match fs::read_to_string(path) {
+                    Ok(text) => map_config.shape = MapShape::Ascii(text),
+                    Err(err) => eprintln!("Error reading map {}\n{}", path, err),
+                },
+                ("--size", size) => match size.split_once('x').map(|(rows, cols)| (rows.parse(), cols.parse())) {
+                    Some((Ok(rows), Ok(cols))) => map_config.shape = MapShape::Rectangle { rows, cols },
+                    _ => eprintln!("Invalid map size {}, expected <rows>x<cols>", size),
+                },
+                ("--hexagon", radius) => match radius.parse() {
+                    Ok(radius) => map_config.shape = MapShape::Hexagon { radius },
+                    Err(err) => eprintln!("Invalid hexagon radius {}\n{}", radius, err),
+                },
+                ("--layout", name) => match HexOffset::from_name(name) {
+                    Some(offset) => map_config.layout.offset = offset,
+                    None => eprintln!("Invalid layout {}, expected odd-r, even-r, odd-q or even-q", name),
+                },
+                _ => {}
+            }
+        }
+        map_config
+    }
+}
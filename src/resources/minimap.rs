@@ -0,0 +1,53 @@
+use bevy::{ecs::system::Resource, math::{Rect, UVec2, Vec2}};
+
+use super::hex::HexGrid;
+
+// Which hex each pixel of the minimap image shows, built once per map
+#[derive(Resource)]
+pub struct MinimapLayout {
+    // World space area the minimap covers
+    pub bounds: Rect,
+    pub size: UVec2,
+    // Row major from the top left, holes and the area around the map are None
+    pub pixels: Vec<Option<(usize, usize)>>
+}
+
+impl MinimapLayout {
+    // Width of the minimap in pixels, the height follows the map's aspect ratio
+    pub const WIDTH: u32 = 200;
+    pub const MAX_HEIGHT: u32 = 300;
+
+    pub fn new(hex_grid: &HexGrid) -> Self {
+        let bounds = hex_grid.world_bounds();
+        let height = (MinimapLayout::WIDTH as f32 * bounds.height() / bounds.width().max(1.0)).round() as u32;
+        let size = UVec2::new(MinimapLayout::WIDTH, height.clamp(1, MinimapLayout::MAX_HEIGHT));
+        let mut layout = Self {
+            bounds,
+            size,
+            pixels: Vec::new()
+        };
+        layout.pixels = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let fraction = Vec2::new((x as f32 + 0.5) / size.x as f32, (y as f32 + 0.5) / size.y as f32);
+                hex_grid.get_at_world(layout.fraction_to_world(fraction))
+            })
+            .collect();
+        layout
+    }
+
+    // Where a world point sits on the minimap, 0 to 1 from the top left
+    pub fn world_to_fraction(&self, point: Vec2) -> Vec2 {
+        Vec2::new(
+            (point.x - self.bounds.min.x) / self.bounds.width(),
+            (self.bounds.max.y - point.y) / self.bounds.height()
+        )
+    }
+
+    pub fn fraction_to_world(&self, fraction: Vec2) -> Vec2 {
+        Vec2::new(
+            self.bounds.min.x + fraction.x * self.bounds.width(),
+            self.bounds.max.y - fraction.y * self.bounds.height()
+        )
+    }
+}
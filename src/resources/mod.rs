@@ -1,4 +1,19 @@
 pub mod asset_handles;
 pub mod hex;
 pub mod input;
-pub mod weather;
\ No newline at end of file
+pub mod weather;
+pub mod replication;
+pub mod build;
+pub mod dispersion;
+pub mod boundary;
+pub mod map;
+pub mod layout;
+pub mod field;
+pub mod sim_clock;
+pub mod sim_rng;
+pub mod replay;
+pub mod history;
+pub mod stats;
+pub mod heatmap;
+pub mod flow;
+pub mod minimap;
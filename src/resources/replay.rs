@@ -0,0 +1,138 @@
+use std::fs;
+use bevy::ecs::system::Resource;
+use rand::{thread_rng, Rng};
+use serde::{Serialize, Deserialize};
+
+use crate::components::game_events::SimCommand;
+
+use super::map::MapConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ReplayMode {
+    // Live play, commands are recorded as they are issued
+    #[default]
+    Recording,
+    // Commands come from the replay file, live input is dropped
+    Playback
+}
+
+// Commands issued since the last time they were applied, see issue_sim_commands
+#[derive(Resource, Default)]
+pub struct SimCommandQueue(pub Vec<SimCommand>);
+
+impl SimCommandQueue {
+    pub fn push(&mut self, command: SimCommand) {
+        self.0.push(command);
+    }
+}
+
+// Seed, map and every command of a run with the tick it was applied after.
+// Rerunning those from the same seed gives the same nanite field and MACC paths
+#[derive(Resource, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub map: MapConfig,
+    // Ticks the recording ran for
+    pub length: u64,
+    pub commands: Vec<(u64, SimCommand)>,
+    #[serde(skip)]
+    pub mode: ReplayMode,
+    #[serde(skip)]
+    record_path: Option<String>,
+    // Next command to play back
+    #[serde(skip)]
+    cursor: usize,
+    // Tick being scrubbed to, ticks run as fast as possible until it is reached
+    #[serde(skip)]
+    pub seek_target: Option<u64>,
+    #[serde(skip)]
+    pub restart_requested: bool
+}
+
+impl Replay {
+    pub fn new(seed: u64, map: MapConfig) -> Self {
+        Self {
+            seed,
+            map,
+            length: 0,
+            commands: Vec::new(),
+            mode: ReplayMode::Recording,
+            record_path: None,
+            cursor: 0,
+            seek_target: None,
+            restart_requested: false
+        }
+    }
+
+    // Reads --seed <number>, --record <file> and --replay <file> from the command line.
+    // A replay brings its own seed and map
+    pub fn from_args(map_config: MapConfig) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut replay = Replay::new(thread_rng().gen(), map_config);
+        for pair in args.windows(2) {
+            match (pair[0].as_str(), pair[1].as_str()) {
+                ("--seed", seed) => match seed.parse() {
+                    Ok(seed) => replay.seed = seed,
+                    Err(err) => eprintln!("Invalid seed {}\n{}", seed, err),
+                },
+                ("--record", path) => replay.record_path = Some(path.to_string()),
+                ("--replay", path) => match Replay::load(path) {
+                    Ok(loaded) => replay = loaded,
+                    Err(err) => eprintln!("Error loading replay {}\n{}", path, err),
+                },
+                _ => {}
+            }
+        }
+        replay
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut replay: Replay = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+        replay.mode = ReplayMode::Playback;
+        Ok(replay)
+    }
+
+    // Writes the recording up to the given tick to the --record path, or replay.json
+    pub fn save(&mut self, tick: u64) -> Result<String, String> {
+        self.length = tick;
+        let path = self.record_path.clone().unwrap_or_else(|| "replay.json".to_string());
+        let text = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&path, text).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+
+    pub fn has_record_path(&self) -> bool {
+        self.record_path.is_some()
+    }
+
+    pub fn is_playback(&self) -> bool {
+        self.mode == ReplayMode::Playback
+    }
+
+    pub fn record(&mut self, tick: u64, command: SimCommand) {
+        self.commands.push((tick, command));
+    }
+
+    // Recorded commands applied after the given tick that haven't been played back yet
+    pub fn due_commands(&mut self, tick: u64) -> Vec<SimCommand> {
+        let start = self.cursor;
+        while self.cursor < self.commands.len() && self.commands[self.cursor].0 <= tick {
+            self.cursor += 1;
+        }
+        self.commands[start..self.cursor].iter().map(|(_, command)| command.clone()).collect()
+    }
+
+    // Scrubbing back has to rerun the simulation from the start
+    pub fn seek(&mut self, target: u64, current_tick: u64) {
+        if target < current_tick {
+            self.restart_requested = true;
+        }
+        self.seek_target = Some(target);
+    }
+
+    pub fn rewind(&mut self) {
+        self.cursor = 0;
+        self.restart_requested = false;
+    }
+}
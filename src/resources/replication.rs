@@ -0,0 +1,17 @@
+use bevy::ecs::system::Resource;
+
+#[derive(Resource)]
+pub struct ReplicationSettings {
+    pub enabled: bool,
+    // Hex resource consumed for every nanite created
+    pub resource_per_nanite: f32
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resource_per_nanite: 0.5
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fmt::Display, time::Duration};
+use bevy::ecs::{system::Resource, schedule::ScheduleLabel};
+
+// Schedule holding one step of the nanite simulation, run by run_sim_ticks
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimTick;
+
+// Schedule applying queued or replayed SimCommands, run before every tick
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ApplySimCommands;
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum SimSpeed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+    // As many ticks as fit in a frame
+    Max
+}
+
+impl SimSpeed {
+    pub const ALL: [SimSpeed; 5] = [
+        SimSpeed::Half,
+        SimSpeed::Normal,
+        SimSpeed::Double,
+        SimSpeed::Quadruple,
+        SimSpeed::Max
+    ];
+
+    // Relative speed of Time<Virtual>, which also drives MACC movement
+    pub fn time_scale(&self) -> f32 {
+        match self {
+            SimSpeed::Half => 0.5,
+            SimSpeed::Normal => 1.0,
+            SimSpeed::Double => 2.0,
+            SimSpeed::Quadruple | SimSpeed::Max => 4.0,
+        }
+    }
+}
+
+impl Display for SimSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimSpeed::Half => write!(f, "0.5x"),
+            SimSpeed::Normal => write!(f, "1x"),
+            SimSpeed::Double => write!(f, "2x"),
+            SimSpeed::Quadruple => write!(f, "4x"),
+            SimSpeed::Max => write!(f, "Max"),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SimClock {
+    // Ticks run so far, the tick being run while inside SimTick
+    pub tick: u64,
+    pub paused: bool,
+    pub speed: SimSpeed,
+    // Seconds of virtual time per tick
+    pub tick_length: f32,
+    accumulator: f32,
+    step_requested: bool
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            paused: false,
+            speed: SimSpeed::default(),
+            tick_length: 1.0,
+            accumulator: 0.0,
+            step_requested: false
+        }
+    }
+}
+
+impl SimClock {
+    // Most ticks run in one frame, slow frames drop the rest instead of falling further behind
+    pub const MAX_TICKS_PER_FRAME: u32 = 64;
+    // Time a frame may spend ticking before the rest are dropped
+    pub const FRAME_BUDGET: Duration = Duration::from_millis(12);
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Runs a single tick on the next frame, only while paused
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+
+    // Ticks to run this frame given the virtual time passed, which is already scaled
+    // by the speed and stopped while paused
+    pub fn ticks_due(&mut self, delta: f32) -> u32 {
+        if self.step_requested {
+            self.step_requested = false;
+            return 1;
+        }
+        if self.paused {
+            return 0;
+        }
+        if self.speed == SimSpeed::Max {
+            return SimClock::MAX_TICKS_PER_FRAME;
+        }
+
+        self.accumulator += delta;
+        let ticks = (self.accumulator / self.tick_length).floor();
+        self.accumulator -= ticks * self.tick_length;
+        (ticks as u32).min(SimClock::MAX_TICKS_PER_FRAME)
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::ecs::system::Resource;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+// Source of every random number the simulation uses, seeded so replays come out the same
+#[derive(Resource)]
+pub struct SimRng(StdRng);
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
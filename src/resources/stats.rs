@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, fmt::Display, fs::File, io::{BufWriter, Write}};
+use bevy::{ecs::system::Resource, render::color::Color};
+use serde::Serialize;
+
+use super::{hex::{HexGrid, NaniteReserve}, field::NaniteField, weather::Weather};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsFormat {
+    Csv,
+    JsonLines
+}
+
+impl StatsFormat {
+    // .csv files get CSV, anything else JSON Lines
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".csv") {
+            StatsFormat::Csv
+        } else {
+            StatsFormat::JsonLines
+        }
+    }
+}
+
+// Aggregate metrics of the field after a tick
+#[derive(Serialize, Debug, Clone)]
+pub struct TickStats {
+    pub tick: u64,
+    pub grid_nanites: f32,
+    pub reserve: f32,
+    pub full_hexes: usize,
+    // Nanites over capacity, averaged over the map
+    pub mean_density: f32,
+    pub max_density: f32,
+    pub wind_direction: f32,
+    pub wind_strength: f32,
+    // Nanites on the hexes under each team's MACCs
+    pub team_a_exposure: f32,
+    pub team_b_exposure: f32
+}
+
+impl TickStats {
+    // Metrics of the field and weather, MACC exposure is left at 0
+    pub fn from_field(tick: u64, hex_grid: &HexGrid, nanite_field: &NaniteField, weather: &Weather, nanite_reserve: &NaniteReserve) -> Self {
+        let mut stats = TickStats {
+            tick,
+            grid_nanites: 0.0,
+            reserve: nanite_reserve.amount,
+            full_hexes: 0,
+            mean_density: 0.0,
+            max_density: 0.0,
+            wind_direction: weather.wind_direction,
+            wind_strength: weather.wind_strength,
+            team_a_exposure: 0.0,
+            team_b_exposure: 0.0
+        };
+        let mut hexes = 0;
+        for nanite in hex_grid.hexes().filter_map(|pos| nanite_field.nanite(pos)) {
+            let density = nanite.nanite_total / nanite.nanite_capacity;
+            hexes += 1;
+            stats.grid_nanites += nanite.nanite_total;
+            stats.mean_density += density;
+            stats.max_density = stats.max_density.max(density);
+            if nanite.is_full() {
+                stats.full_hexes += 1;
+            }
+        }
+        if hexes > 0 {
+            stats.mean_density /= hexes as f32;
+        }
+        stats
+    }
+
+    pub const CSV_HEADER: &'static str = "tick,grid_nanites,reserve,full_hexes,mean_density,max_density,wind_direction,wind_strength,team_a_exposure,team_b_exposure";
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.tick, self.grid_nanites, self.reserve, self.full_hexes, self.mean_density,
+            self.max_density, self.wind_direction, self.wind_strength, self.team_a_exposure, self.team_b_exposure
+        )
+    }
+}
+
+// TickStats plotted on the charts panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsMetric {
+    GridNanites,
+    Reserve,
+    FullHexes,
+    WindStrength
+}
+
+impl StatsMetric {
+    pub const ALL: [StatsMetric; 4] = [
+        StatsMetric::GridNanites,
+        StatsMetric::Reserve,
+        StatsMetric::FullHexes,
+        StatsMetric::WindStrength
+    ];
+
+    pub fn value(&self, stats: &TickStats) -> f32 {
+        match self {
+            StatsMetric::GridNanites => stats.grid_nanites,
+            StatsMetric::Reserve => stats.reserve,
+            StatsMetric::FullHexes => stats.full_hexes as f32,
+            StatsMetric::WindStrength => stats.wind_strength,
+        }
+    }
+
+    // Line color on the charts panel
+    pub fn color(&self) -> Color {
+        match self {
+            StatsMetric::GridNanites => Color::DARK_GREEN,
+            StatsMetric::Reserve => Color::NAVY,
+            StatsMetric::FullHexes => Color::MAROON,
+            StatsMetric::WindStrength => Color::INDIGO,
+        }
+    }
+}
+
+impl Display for StatsMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsMetric::GridNanites => write!(f, "Grid Nanites"),
+            StatsMetric::Reserve => write!(f, "Reserve"),
+            StatsMetric::FullHexes => write!(f, "Saturated Hexes"),
+            StatsMetric::WindStrength => write!(f, "Wind Strength"),
+        }
+    }
+}
+
+// TickStats of the last WINDOW_MINUTES of sim time, newest at the back, each with the
+// nanite total of every hex after that tick in row major order, see HexGrid::index
+#[derive(Resource, Default)]
+pub struct StatsHistory {
+    ticks: VecDeque<(TickStats, Vec<f32>)>
+}
+
+impl StatsHistory {
+    pub const WINDOW_MINUTES: f32 = 5.0;
+
+    pub fn record(&mut self, stats: TickStats, hex_nanites: Vec<f32>, tick_length: f32) {
+        // Charts start over with the run, drop what a restarted run hasn't caught up to
+        while self.ticks.back().is_some_and(|(last, _)| last.tick >= stats.tick) {
+            self.ticks.pop_back();
+        }
+        let window = (StatsHistory::WINDOW_MINUTES * 60.0 / tick_length).ceil() as u64;
+        while self.ticks.front().is_some_and(|(first, _)| first.tick + window <= stats.tick) {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back((stats, hex_nanites));
+    }
+
+    pub fn latest(&self) -> Option<&TickStats> {
+        self.ticks.back().map(|(stats, _)| stats)
+    }
+
+    // Oldest first
+    pub fn series(&self, metric: StatsMetric) -> Vec<f32> {
+        self.ticks.iter().map(|(stats, _)| metric.value(stats)).collect()
+    }
+
+    // Nanite total of one hex, oldest first
+    pub fn hex_series(&self, index: usize) -> Vec<f32> {
+        self.ticks.iter().map(|(_, hex_nanites)| hex_nanites.get(index).copied().unwrap_or(0.0)).collect()
+    }
+}
+
+// State of a single hex after a tick
+#[derive(Serialize, Debug)]
+pub struct HexStats {
+    pub tick: u64,
+    pub row: usize,
+    pub col: usize,
+    pub nanites: f32,
+    pub density: f32,
+    pub resource: f32
+}
+
+impl HexStats {
+    pub const CSV_HEADER: &'static str = "tick,row,col,nanites,density,resource";
+
+    fn to_csv(&self) -> String {
+        format!("{},{},{},{},{},{}", self.tick, self.row, self.col, self.nanites, self.density, self.resource)
+    }
+}
+
+// Buffered CSV or JSON Lines output, see StatsFormat::from_path
+pub struct StatsFile {
+    writer: BufWriter<File>,
+    format: StatsFormat
+}
+
+impl StatsFile {
+    pub fn create(path: &str, csv_header: &str) -> Option<Self> {
+        let format = StatsFormat::from_path(path);
+        let mut writer = match File::create(path) {
+            Ok(file) => BufWriter::new(file),
+            Err(err) => {
+                eprintln!("Error creating stats file {}\n{}", path, err);
+                return None;
+            },
+        };
+        if format == StatsFormat::Csv {
+            if let Err(err) = writeln!(writer, "{}", csv_header) {
+                eprintln!("Error writing stats file {}\n{}", path, err);
+            }
+        }
+        Some(Self { writer, format })
+    }
+
+    pub fn write_line(&mut self, csv: impl FnOnce() -> String, record: &impl Serialize) {
+        let line = match self.format {
+            StatsFormat::Csv => csv(),
+            StatsFormat::JsonLines => match serde_json::to_string(record) {
+                Ok(line) => line,
+                Err(err) => {
+                    eprintln!("Error serializing stats\n{}", err);
+                    return;
+                },
+            },
+        };
+        if let Err(err) = writeln!(self.writer, "{}", line) {
+            eprintln!("Error writing stats\n{}", err);
+        }
+    }
+    pub fn flush(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("Error flushing stats\n{}", err);
+        }
+    }
+}
+
+// Writes TickStats and HexStats for every hex each tick, to whichever files were asked for
+#[derive(Resource)]
+pub struct StatsRecorder {
+    ticks: Option<StatsFile>,
+    hexes: Option<StatsFile>
+}
+
+impl StatsRecorder {
+    // Reads --stats <file> and --hex-stats <file> from the command line,
+    // files ending in .csv are written as CSV and the rest as JSON Lines
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let mut ticks = None;
+        let mut hexes = None;
+        for pair in args.windows(2) {
+            match (pair[0].as_str(), pair[1].as_str()) {
+                ("--stats", path) => ticks = StatsFile::create(path, TickStats::CSV_HEADER),
+                ("--hex-stats", path) => hexes = StatsFile::create(path, HexStats::CSV_HEADER),
+                _ => {}
+            }
+        }
+        if ticks.is_none() && hexes.is_none() {
+            return None;
+        }
+        Some(Self { ticks, hexes })
+    }
+
+    pub fn records_hexes(&self) -> bool {
+        self.hexes.is_some()
+    }
+
+    pub fn write_tick(&mut self, stats: &TickStats) {
+        if let Some(ticks) = self.ticks.as_mut() {
+            ticks.write_line(|| stats.to_csv(), stats);
+        }
+    }
+
+    pub fn write_hex(&mut self, stats: &HexStats) {
+        if let Some(hexes) = self.hexes.as_mut() {
+            hexes.write_line(|| stats.to_csv(), stats);
+        }
+    }
+
+    pub fn flush(&mut self) {
+        for file in self.ticks.iter_mut().chain(self.hexes.iter_mut()) {
+            file.flush();
+        }
+    }
+}
@@ -1,15 +1,14 @@
 use bevy::ecs::system::Resource;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct Weather {
     pub wind_strength: f32,
     pub wind_direction: f32
 }
 
 impl Weather {
-    pub fn adjust_wind(&mut self) {
-        let mut rng = thread_rng();
+    pub fn adjust_wind(&mut self, rng: &mut impl Rng) {
         let mut dir = self.wind_direction + rng.gen_range(-90.0..90.0);
         if dir < 0.0 {
             dir += 360.0;
@@ -1,72 +1,252 @@
-use bevy::{ecs::{system::{Query, ResMut, Res}, query::{Changed, With}, entity::Entity, event::EventReader}, hierarchy::Children, asset::{Handle, Assets}, sprite::ColorMaterial, render::color::Color, input::{keyboard::KeyCode, Input}, transform::components::Transform};
-use rand::{thread_rng, Rng, seq::SliceRandom};
+use std::{collections::{HashMap, HashSet}, time::Instant};
+use bevy::{ecs::{system::{Query, ResMut, Res, Commands, Local}, query::{With, Or}, entity::Entity, event::{EventReader, EventWriter}, change_detection::{DetectChanges, Ref}, world::World, system::RunSystemOnce}, time::{Time, Virtual, Real}, app::AppExit, input::{Input, keyboard::KeyCode}, asset::Assets, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, ui::{Style, Val, UiImage}, text::{Text, Text2dBundle, TextStyle}, render::{color::Color, mesh::{Mesh, Indices}, render_resource::{PrimitiveTopology, Extent3d, TextureDimension, TextureFormat}, texture::Image, camera::OrthographicProjection}, transform::components::Transform, gizmos::gizmos::Gizmos, math::{Vec2, Rect, Quat}, prelude::default, tasks::{ComputeTaskPool, TaskPool, ParallelSlice}};
+use rand::{Rng, seq::SliceRandom};
 
-use crate::{components::{nanite::Nanite, grid_pos::GridPos, terrain::Terrain, macc::Macc, game_events::GameEvents}, resources::{hex::{HexGrid, NaniteReserve, MapState}, weather::Weather, input::SelectedMacc}};
+use crate::{components::{grid_pos::GridPos, nanite::Nanite, macc::{Macc, MaccId, MaccStatus, Team}, game_events::{GameEvents, SimCommand}, chunk::{ChunkRender, HexValueLabel}, ui::Minimap, structure::{Barrier, Emitter, Scrubber, StructureKind}}, resources::{hex::{HexGrid, HexDirection, NaniteReserve, MapState, EdgeAttribute, EdgeKey}, field::{NaniteField, CHUNK_SIZE}, layout::HexLayout, asset_handles::AssetHandles, weather::Weather, input::SelectedMacc, replication::ReplicationSettings, build::BuildFunds, dispersion::{DispersionSettings, DispersionMode}, boundary::BoundaryKind, sim_clock::{SimClock, SimSpeed, SimTick, ApplySimCommands}, sim_rng::SimRng, replay::{Replay, SimCommandQueue}, history::{FieldHistory, HistoryEntry}, stats::{StatsRecorder, StatsHistory, TickStats, HexStats}, heatmap::{HeatmapSettings, danger_color}, flow::{NaniteFlow, EdgeFlow, FlowOverlay}, minimap::MinimapLayout}};
+
+use super::startup_systems::{MainCamera, setup_simulation, spawn_hexagons};
+
+// Hexes handed to each task of the parallel passes
+const PAR_BATCH_SIZE: usize = 1024;
+
+// Maps every hex to a value across the compute task pool. The result is indexed like
+// HexGrid::index with holes left at the default, and doesn't depend on how the work is split
+fn par_map_hexes<T, F>(hex_grid: &HexGrid, f: F) -> Vec<T>
+where
+    T: Default + Send + 'static,
+    F: Fn((usize, usize)) -> T + Send + Sync
+{
+    let positions: Vec<(usize, usize)> = hex_grid.hexes().collect();
+    let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    let values = positions.par_chunk_map(task_pool, PAR_BATCH_SIZE, |batch| {
+        batch.iter().map(|pos| f(*pos)).collect::<Vec<T>>()
+    });
+
+    let mut dense: Vec<T> = (0..hex_grid.cells.len()).map(|_| T::default()).collect();
+    for (pos, value) in positions.iter().zip(values.into_iter().flatten()) {
+        dense[hex_grid.index(*pos)] = value;
+    }
+    dense
+}
+
+// Where the nanites blown out of a hex end up
+#[derive(Clone, Copy, Default, PartialEq)]
+enum WindSink {
+    // Nothing leaves, walls or reflective edges hold it in
+    #[default]
+    Stay,
+    Hex(usize),
+    Reserve,
+    // Blown off an absorbing edge and destroyed
+    Destroyed
+}
 
 pub fn nanite_wind(
     hex_grid: Res<HexGrid>,
     weather: Res<Weather>,
     mut nanite_reserve: ResMut<NaniteReserve>,
-    grid_pos_q: Query<&GridPos>,
-    mut nanite_q: Query<&mut Nanite>
+    mut nanite_field: ResMut<NaniteField>,
+    mut nanite_flow: ResMut<NaniteFlow>
 ) {
-    for rows in hex_grid.grid.iter() {
-        for current_entity in rows.iter() {
-            let grid_pos = grid_pos_q.get(*current_entity).unwrap();
-            let neighbor = hex_grid.get_wind_neighors_new(grid_pos, weather.wind_direction);
-            let nanite_pool = nanite_q.get_mut(*current_entity).unwrap().wind_pull(weather.wind_strength);
-            
-            match neighbor {
-                Some(ent) => nanite_q.get_mut(ent).unwrap().add_transient_nanites(nanite_pool),
-                None => nanite_reserve.add_nanites(nanite_pool),
+    let wind_direction = hex_grid.layout.direction_from_angle(weather.wind_direction);
+    let snapshot = nanite_field.snapshot();
+
+    // Where each hex blows to and how strongly
+    let flows: Vec<(WindSink, f32)> = par_map_hexes(&hex_grid, |pos| {
+        let grid_pos = GridPos { pos };
+        // Walls and filters hold back part of what the wind would carry
+        let strength = weather.wind_strength * hex_grid.edge_permeability(&grid_pos, wind_direction);
+        if strength <= 0.0 {
+            return (WindSink::Stay, 0.0);
+        }
+        let sink = match hex_grid.get_wind_neighors_new(&grid_pos, weather.wind_direction) {
+            Some(neighbor_pos) => WindSink::Hex(hex_grid.index(neighbor_pos)),
+            None => match hex_grid.crossed_boundary(&grid_pos, wind_direction) {
+                // Reflective edges, and periodic ones wrapping onto a hole, keep the nanites on the map
+                Some(BoundaryKind::Reflective | BoundaryKind::Periodic) => return (WindSink::Stay, 0.0),
+                Some(BoundaryKind::Absorbing) => WindSink::Destroyed,
+                _ => WindSink::Reserve
             }
+        };
+        (sink, strength)
+    });
+
+    // Each hex gathers what its upwind neighbor blew into it
+    let upwind = wind_direction.opposite();
+    let incoming: Vec<f32> = par_map_hexes(&hex_grid, |pos| {
+        let index = hex_grid.index(pos);
+        hex_grid.get_neigbors(&GridPos { pos }).some_neighbors()
+            .filter(|(direction, _)| *direction == upwind)
+            .map(|(_, neighbor_pos)| hex_grid.index(neighbor_pos))
+            .filter(|neighbor| flows[*neighbor].0 == WindSink::Hex(index))
+            .map(|neighbor| snapshot[neighbor] * flows[neighbor].1.clamp(0.0, 1.0))
+            .sum()
+    });
+
+    let mut net = incoming.clone();
+    let mut wind_edges = Vec::new();
+    for pos in hex_grid.hexes() {
+        let index = hex_grid.index(pos);
+        let (sink, strength) = flows[index];
+        let nanite = nanite_field.nanite_mut(pos).unwrap();
+        let nanite_pool = nanite.wind_pull(strength);
+        if sink == WindSink::Reserve {
+            nanite_reserve.add_nanites(nanite_pool);
+        }
+        nanite.add_transient_nanites(incoming[index]);
+        net[index] -= nanite_pool;
+        // Nanites staying put aren't drawn, the ones blown off the map are
+        if sink != WindSink::Stay && nanite_pool > 0.0 {
+            wind_edges.push(EdgeFlow { pos, direction: wind_direction, amount: nanite_pool });
         }
     }
+    nanite_flow.wind = net;
+    nanite_flow.wind_edges = wind_edges;
 }
 
 pub fn nanite_dispersion(
     hex_grid: Res<HexGrid>,
-    grid_pos_q: Query<&GridPos>,
-    mut nanite_q: Query<&mut Nanite>
+    dispersion_settings: Res<DispersionSettings>,
+    mut nanite_field: ResMut<NaniteField>,
+    mut nanite_flow: ResMut<NaniteFlow>
 ) {
-    for rows in hex_grid.grid.iter() {
-        for current_ent in rows.iter() {
-            if !nanite_q.get(*current_ent).unwrap().is_full() {
-                continue;
-            }
+    // Every hex reads from the same snapshot so the result doesn't depend on iteration order
+    let snapshot = nanite_field.snapshot();
+    let links: Vec<DispersionLinks> = par_map_hexes(&hex_grid, |pos| dispersion_links(&hex_grid, &GridPos { pos }));
 
-            let grid_pos = grid_pos_q.get(*current_ent).unwrap();
-            let neighbors = hex_grid.get_neigbors(grid_pos);
-            
-            let mut low_neighbors: Vec<Entity> = Vec::with_capacity(6);
-            for neighbor_ent in neighbors.some_neighbors() {
-                let [curr_nan, neigh_nan] = nanite_q.get_many([current_ent.clone(), neighbor_ent]).unwrap();
-                if curr_nan.nanite_total > neigh_nan.nanite_total {
-                    low_neighbors.push(neighbor_ent.clone());
-                }
-            }
-            let nanites_to_add = nanite_q.get_mut(*current_ent).unwrap().spill() / low_neighbors.len() as f32;
-            
-            low_neighbors.iter().for_each(|neigh_ent| {
-                nanite_q.get_mut(*neigh_ent).unwrap().add_transient_nanites(nanites_to_add);
+    let (deltas, link_flows) = match dispersion_settings.mode {
+        DispersionMode::Overflow => overflow_flows(&hex_grid, &nanite_field, &links, &snapshot),
+        DispersionMode::Diffusion => diffusion_flows(&hex_grid, &dispersion_settings, &links, &snapshot),
+    };
+
+    for pos in hex_grid.hexes() {
+        nanite_field.nanite_mut(pos).unwrap().add_transient_nanites(deltas[hex_grid.index(pos)]);
+    }
+    nanite_flow.dispersion = deltas;
+    // Every edge shows up on both sides, keep the side it leaves from
+    nanite_flow.dispersion_edges = hex_grid.hexes()
+        .flat_map(|pos| {
+            let index = hex_grid.index(pos);
+            links[index].iter().zip(&link_flows[index])
+                .filter(|(_, flow)| **flow < 0.0)
+                .map(move |((direction, _, _), flow)| EdgeFlow { pos, direction: *direction, amount: -flow })
+        })
+        .collect();
+}
+
+// Overflow only runs downhill, absorbing sinks are always lower
+fn overflows_into(snapshot: &[f32], from: usize, to: Option<usize>) -> bool {
+    to.is_none_or(|to| snapshot[from] > snapshot[to])
+}
+
+// Change of every hex's total, and what it gained over each of its links,
+// negative where it lost nanites. Row major like the snapshot
+type DispersionFlows = (Vec<f32>, Vec<Vec<f32>>);
+
+fn overflow_flows(
+    hex_grid: &HexGrid,
+    nanite_field: &NaniteField,
+    links: &[DispersionLinks],
+    snapshot: &[f32]
+) -> DispersionFlows {
+    // Overflow a full hex sends over each edge to a lower neighbor, before filters
+    let shares: Vec<f32> = par_map_hexes(hex_grid, |pos| {
+        let index = hex_grid.index(pos);
+        let nanite = nanite_field.nanite(pos).unwrap();
+        let low_neighbors = links[index].iter()
+            .filter(|(_, neighbor, _)| overflows_into(snapshot, index, *neighbor))
+            .count();
+        if !nanite.is_full() || low_neighbors == 0 {
+            return 0.0;
+        }
+        nanite.overflow() / low_neighbors as f32
+    });
+
+    // Whatever a filter holds back stays in the hex
+    let link_flows: Vec<Vec<f32>> = par_map_hexes(hex_grid, |pos| {
+        let index = hex_grid.index(pos);
+        links[index].iter()
+            .map(|(_, neighbor, permeability)| {
+                let outgoing = if overflows_into(snapshot, index, *neighbor) { shares[index] } else { 0.0 };
+                let incoming = match neighbor {
+                    Some(neighbor) if overflows_into(snapshot, *neighbor, Some(index)) => shares[*neighbor],
+                    _ => 0.0
+                };
+                (incoming - outgoing) * permeability
             })
+            .collect()
+    });
+    let deltas = link_flows.iter().map(|flows| flows.iter().sum()).collect();
+    (deltas, link_flows)
+}
+
+// Edge direction and row major index of neighbors, see HexGrid::index
+type DispersionLinks = Vec<(HexDirection, Option<usize>, f32)>;
+
+// Neighbors a hex can disperse into along with the permeability of the shared edge,
+// absorbing map edges show up as an always empty sink
+fn dispersion_links(hex_grid: &HexGrid, grid_pos: &GridPos) -> DispersionLinks {
+    let sinks = hex_grid.layout.directions().into_iter()
+        .filter(|direction| hex_grid.crossed_boundary(grid_pos, *direction) == Some(BoundaryKind::Absorbing))
+        .map(|direction| (direction, None));
+    hex_grid.get_neigbors(grid_pos).some_neighbors()
+        .map(|(direction, neighbor_pos)| (direction, Some(hex_grid.index(neighbor_pos))))
+        .chain(sinks)
+        .map(|(direction, neighbor)| (direction, neighbor, hex_grid.edge_permeability(grid_pos, direction)))
+        .filter(|(_, _, permeability)| *permeability > 0.0)
+        .collect()
+}
+
+fn diffusion_flows(
+    hex_grid: &HexGrid,
+    dispersion_settings: &DispersionSettings,
+    links: &[DispersionLinks],
+    snapshot: &[f32]
+) -> DispersionFlows {
+    let substeps = dispersion_settings.substeps();
+    let rate = dispersion_settings.coefficient * dispersion_settings.timestep / substeps as f32;
+
+    let mut totals = snapshot.to_vec();
+    let mut link_flows: Vec<Vec<f32>> = links.iter().map(|links| vec![0.0; links.len()]).collect();
+    for _ in 0..substeps {
+        // Fluxes read the last substep's totals and land in their own buffer
+        let fluxes: Vec<Vec<f32>> = par_map_hexes(hex_grid, |pos| {
+            let index = hex_grid.index(pos);
+            links[index].iter()
+                .map(|(_, neighbor, permeability)| {
+                    let neighbor_total = neighbor.map_or(0.0, |neighbor| totals[neighbor]);
+                    rate * permeability * (neighbor_total - totals[index])
+                })
+                .collect()
+        });
+        for ((total, flows), fluxes) in totals.iter_mut().zip(link_flows.iter_mut()).zip(fluxes) {
+            *total += fluxes.iter().sum::<f32>();
+            for (flow, flux) in flows.iter_mut().zip(fluxes) {
+                *flow += flux;
+            }
         }
     }
+
+    let deltas = totals.iter().zip(snapshot)
+        .map(|(total, before)| total - before)
+        .collect();
+    (deltas, link_flows)
 }
 
 pub fn nanite_introduction(
     hex_grid: Res<HexGrid>,
     weather: Res<Weather>,
     mut nanite_reserve: ResMut<NaniteReserve>,
-    mut nanite_q: Query<&mut Nanite>
+    mut nanite_field: ResMut<NaniteField>,
+    mut sim_rng: ResMut<SimRng>
 ) {
-    let mut rng = thread_rng();
-    let mut nanite_pool = nanite_reserve.pull();
-    let edges = hex_grid.direction_edges(weather.wind_direction + 180.0);
+    let rng = &mut *sim_rng;
+    let mut nanite_pool = nanite_reserve.pull(rng);
+    let edges = hex_grid.direction_edges(weather.wind_direction + 180.0, BoundaryKind::Reservoir);
 
     while nanite_pool > 0.0 && !edges.is_empty() {
-        let ent = edges.choose(&mut rng).unwrap();
-        let mut nanite = nanite_q.get_mut(*ent).unwrap();
+        let pos = edges.choose(rng).unwrap();
+        let nanite = nanite_field.nanite_mut(*pos).unwrap();
         
         let amount = if nanite_pool <= 5.0 {
             nanite_pool
@@ -79,127 +259,1263 @@ pub fn nanite_introduction(
     }
 }
 
+pub fn nanite_replication(
+    replication_settings: Res<ReplicationSettings>,
+    mut nanite_field: ResMut<NaniteField>
+) {
+    for (terrain, nanite, hex_resource) in nanite_field.cells_mut() {
+        if nanite.nanite_total <= 0.0 {
+            continue;
+        }
+        let max_growth = hex_resource.amount / replication_settings.resource_per_nanite;
+        let growth = nanite.replicate(terrain.replication_rate(), max_growth);
+        hex_resource.consume(growth * replication_settings.resource_per_nanite);
+        nanite.add_transient_nanites(growth);
+    }
+}
+
+pub fn hex_resource_regen(
+    mut nanite_field: ResMut<NaniteField>
+) {
+    for (_, _, hex_resource) in nanite_field.cells_mut() {
+        if hex_resource.amount < hex_resource.max_amount {
+            hex_resource.regenerate();
+        }
+    }
+}
+
+pub fn emitter_inject(
+    mut nanite_field: ResMut<NaniteField>,
+    emitter_q: Query<(&Emitter, &GridPos)>
+) {
+    for (emitter, grid_pos) in emitter_q.iter() {
+        if let Some(nanite) = nanite_field.nanite_mut(grid_pos.pos) {
+            nanite.add_transient_nanites(emitter.rate);
+        }
+    }
+}
+
+pub fn scrubber_filter(
+    hex_grid: Res<HexGrid>,
+    mut nanite_field: ResMut<NaniteField>,
+    scrubber_q: Query<(&Scrubber, &GridPos)>
+) {
+    for (scrubber, scrubber_pos) in scrubber_q.iter() {
+        for pos in hex_grid.hexes_within(scrubber_pos.pos, scrubber.radius) {
+            nanite_field.nanite_mut(pos).unwrap().scrub(scrubber.rate);
+        }
+    }
+}
+
+pub fn build_funds_income(
+    mut build_funds: ResMut<BuildFunds>
+) {
+    build_funds.amount += build_funds.income;
+}
+
 pub fn nanite_transient_apply(
-    mut nanite_q: Query<&mut Nanite, Changed<Nanite>>
+    mut nanite_field: ResMut<NaniteField>
 ) {
-    for mut nanite in nanite_q.iter_mut() {
+    for (_, nanite, _) in nanite_field.cells_mut() {
         nanite.apply_transient_nanites();
     }
 }
 
+// Keeps the field after every tick so the map can look back at it
+pub fn record_history(
+    sim_clock: Res<SimClock>,
+    nanite_field: Res<NaniteField>,
+    weather: Res<Weather>,
+    nanite_flow: Res<NaniteFlow>,
+    mut field_history: ResMut<FieldHistory>
+) {
+    field_history.record(HistoryEntry {
+        tick: sim_clock.tick,
+        dimensions: nanite_field.dimensions,
+        nanites: nanite_field.nanite_snapshot(),
+        flow: nanite_flow.snapshot(nanite_field.dimensions.0 * nanite_field.dimensions.1),
+        weather: weather.clone()
+    });
+}
+
+type MaccTeamQuery<'a> = (&'a Macc, &'a Team);
+
+// Logs the aggregate metrics of the tick, and every hex when asked to. The charts panel
+// plots them from the StatsHistory
+pub fn record_stats(
+    sim_clock: Res<SimClock>,
+    hex_grid: Res<HexGrid>,
+    nanite_field: Res<NaniteField>,
+    weather: Res<Weather>,
+    nanite_reserve: Res<NaniteReserve>,
+    macc_q: Query<MaccTeamQuery>,
+    (mut stats_recorder, stats_history): (Option<ResMut<StatsRecorder>>, Option<ResMut<StatsHistory>>)
+) {
+    let mut stats = TickStats::from_field(sim_clock.tick, &hex_grid, &nanite_field, &weather, &nanite_reserve);
+    if let Some(stats_recorder) = stats_recorder.as_mut().filter(|stats_recorder| stats_recorder.records_hexes()) {
+        for pos in hex_grid.hexes() {
+            if let Some(nanite) = nanite_field.nanite(pos) {
+                stats_recorder.write_hex(&HexStats {
+                    tick: sim_clock.tick,
+                    row: pos.0,
+                    col: pos.1,
+                    nanites: nanite.nanite_total,
+                    density: nanite.nanite_total / nanite.nanite_capacity,
+                    resource: nanite_field.resource(pos).map_or(0.0, |resource| resource.amount)
+                });
+            }
+        }
+    }
+
+    for (macc, team) in macc_q.iter() {
+        let exposure = exposure_at(&hex_grid, &nanite_field, macc.position);
+        match team {
+            Team::A => stats.team_a_exposure += exposure,
+            Team::B => stats.team_b_exposure += exposure,
+        }
+    }
+    if let Some(stats_recorder) = stats_recorder.as_mut() {
+        stats_recorder.write_tick(&stats);
+    }
+    if let Some(mut stats_history) = stats_history {
+        stats_history.record(stats, nanite_field.snapshot(), sim_clock.tick_length);
+    }
+}
+
+// Stats files are buffered, the last ticks are written out on exit
+pub fn flush_stats(
+    mut exit_events: EventReader<AppExit>,
+    mut stats_recorder: ResMut<StatsRecorder>
+) {
+    if exit_events.read().count() > 0 {
+        stats_recorder.flush();
+    }
+}
+
+// Runs the SimTick schedule as many times as the SimClock says are due this frame,
+// or towards the tick a replay is being scrubbed to. Commands are applied before every tick
+pub fn run_sim_ticks(world: &mut World) {
+    if world.resource::<Replay>().restart_requested {
+        restart_simulation(world);
+    }
+    world.run_schedule(ApplySimCommands);
+
+    let delta = world.resource::<Time<Virtual>>().delta_seconds();
+    let tick = world.resource::<SimClock>().tick;
+    let ticks_due = match world.resource::<Replay>().seek_target {
+        Some(target) => target.saturating_sub(tick).min(SimClock::MAX_TICKS_PER_FRAME as u64) as u32,
+        None => world.resource_mut::<SimClock>().ticks_due(delta)
+    };
+    // Playback has no input past the end of the recording
+    let ticks_due = match world.resource::<Replay>() {
+        replay if replay.is_playback() => ticks_due.min(replay.length.saturating_sub(tick).min(u32::MAX as u64) as u32),
+        _ => ticks_due
+    };
+    let frame_start = Instant::now();
+    for _ in 0..ticks_due {
+        world.resource_mut::<SimClock>().tick += 1;
+        world.run_schedule(SimTick);
+        world.run_schedule(ApplySimCommands);
+        if frame_start.elapsed() >= SimClock::FRAME_BUDGET {
+            break;
+        }
+    }
+
+    let tick = world.resource::<SimClock>().tick;
+    let mut replay = world.resource_mut::<Replay>();
+    if replay.seek_target.is_some_and(|target| tick >= target) {
+        replay.seek_target = None;
+    }
+    // Stop where the recording ended, seeks past it stop there too
+    let ended = replay.is_playback() && tick >= replay.length;
+    if ended {
+        replay.seek_target = None;
+        world.resource_mut::<SimClock>().paused = true;
+    }
+}
+
+// Puts the simulation back to tick 0 of the replay seed
+fn restart_simulation(world: &mut World) {
+    let sim_entities: Vec<Entity> = world.query_filtered::<Entity, Or<(With<Macc>, With<GridPos>)>>()
+        .iter(world)
+        .collect();
+    for entity in sim_entities {
+        world.despawn(entity);
+    }
+
+    let seed = world.resource::<Replay>().seed;
+    world.insert_resource(SimRng::new(seed));
+    world.run_system_once(setup_simulation);
+    world.run_system_once(spawn_hexagons);
+    world.resource_mut::<SimClock>().tick = 0;
+    world.resource_mut::<Replay>().rewind();
+}
+
+// Applies the commands queued since last time, or the recorded ones when playing back
+pub fn issue_sim_commands(
+    sim_clock: Res<SimClock>,
+    mut sim_command_queue: ResMut<SimCommandQueue>,
+    mut replay: ResMut<Replay>,
+    mut sim_command_writer: EventWriter<SimCommand>
+) {
+    if replay.is_playback() {
+        sim_command_queue.0.clear();
+        for command in replay.due_commands(sim_clock.tick) {
+            sim_command_writer.send(command);
+        }
+        return;
+    }
+    for command in sim_command_queue.0.drain(..) {
+        replay.record(sim_clock.tick, command.clone());
+        sim_command_writer.send(command);
+    }
+}
+
+pub fn sim_command_react(
+    mut sim_commands: EventReader<SimCommand>,
+    mut selected_macc: ResMut<SelectedMacc>,
+    mut replication_settings: ResMut<ReplicationSettings>,
+    mut dispersion_settings: ResMut<DispersionSettings>,
+    mut hex_grid: ResMut<HexGrid>,
+    mut macc_q: Query<(Entity, &MaccId, &mut Macc)>
+) {
+    for command in sim_commands.read() {
+        match command {
+            SimCommand::MaccSelect(id) => {
+                match macc_q.iter().find(|(_, macc_id, ..)| macc_id.0 == *id) {
+                    Some((ent, ..)) => selected_macc.select(ent),
+                    None => eprintln!("No macc with id {}", id),
+                }
+            },
+            SimCommand::MaccDeselect => selected_macc.deselect(),
+            SimCommand::MaccMoveOrder((x, y)) => {
+                if let Some(selected_macc) = selected_macc.get() {
+                    match macc_q.get_mut(selected_macc) {
+                        Ok((_, _, mut macc)) if !macc.hold => {
+                            macc.target_position = Vec2::new(*x, *y);
+                            macc.waypoints.clear();
+                        },
+                        Ok(_) => {},
+                        Err(err) => eprintln!("Error querying macc {}", err),
+                    }
+                }
+            },
+            SimCommand::MaccQueueWaypoint((x, y)) => {
+                if let Some(selected_macc) = selected_macc.get() {
+                    match macc_q.get_mut(selected_macc) {
+                        Ok((_, _, mut macc)) if !macc.hold => macc.waypoints.push(Vec2::new(*x, *y)),
+                        Ok(_) => {},
+                        Err(err) => eprintln!("Error querying macc {}", err),
+                    }
+                }
+            },
+            SimCommand::MaccOrder(id, order) => {
+                match macc_q.iter_mut().find(|(_, macc_id, ..)| macc_id.0 == *id) {
+                    Some((_, _, mut macc)) => {
+                        let position = macc.position;
+                        macc.order(*order, position);
+                    },
+                    None => eprintln!("No macc with id {}", id),
+                }
+            },
+            SimCommand::BuildStructure(_, _) => {},
+            SimCommand::ToggleReplication => replication_settings.enabled = !replication_settings.enabled,
+            SimCommand::ToggleDispersionMode => dispersion_settings.toggle_mode(),
+            SimCommand::SetDispersionCoefficient(coefficient) => dispersion_settings.coefficient = *coefficient,
+            SimCommand::CycleBoundary(edge) => hex_grid.cycle_boundary(*edge),
+            SimCommand::CycleEdge(pos, direction) => {
+                let attribute = hex_grid.get_edge(*pos, *direction).cycle();
+                hex_grid.set_edge(*pos, *direction, attribute);
+            },
+            SimCommand::StepEdgeFilter(pos, direction, steps) => {
+                let attribute = hex_grid.get_edge(*pos, *direction).step_filter(*steps);
+                hex_grid.set_edge(*pos, *direction, attribute);
+            },
+        }
+    }
+}
+
+// Writes the recording on F5, and on exit when started with --record
+pub fn save_replay(
+    keys: Res<Input<KeyCode>>,
+    mut exit_events: EventReader<AppExit>,
+    sim_clock: Res<SimClock>,
+    mut replay: ResMut<Replay>
+) {
+    let exiting = exit_events.read().count() > 0;
+    if replay.is_playback() || !(keys.just_pressed(KeyCode::F5) || (exiting && replay.has_record_path())) {
+        return;
+    }
+    match replay.save(sim_clock.tick) {
+        Ok(path) => println!("Saved replay to {}", path),
+        Err(err) => eprintln!("Error saving replay\n{}", err),
+    }
+}
+
+// Pauses and scales virtual time along with the sim so eased MACC sprites and other
+// virtual time follow it
+pub fn sync_virtual_time(
+    sim_clock: Res<SimClock>,
+    mut virtual_time: ResMut<Time<Virtual>>
+) {
+    if sim_clock.paused && !virtual_time.is_paused() {
+        virtual_time.pause();
+    } else if !sim_clock.paused && virtual_time.is_paused() {
+        virtual_time.unpause();
+    }
+    if virtual_time.relative_speed() != sim_clock.speed.time_scale() {
+        virtual_time.set_relative_speed(sim_clock.speed.time_scale());
+    }
+}
+
 pub fn adjust_wind(
-    mut weather: ResMut<Weather>
+    mut weather: ResMut<Weather>,
+    mut sim_rng: ResMut<SimRng>
 ) {
-    weather.adjust_wind();
+    weather.adjust_wind(&mut *sim_rng);
 }
 
+// Turns player input into SimCommands, applied by run_sim_ticks
 pub fn game_event_react(
     mut game_events: EventReader<GameEvents>,
-    mut selected_macc: ResMut<SelectedMacc>,
-    mut macc_q: Query<&mut Macc>
+    mut sim_command_queue: ResMut<SimCommandQueue>,
+    macc_q: Query<&MaccId>
 ) {
     for event in game_events.read() {
         match event {
             GameEvents::HexSelect(_) => {},
             GameEvents::MaccSelect(ent) => {
-                selected_macc.select(ent.clone());
+                match macc_q.get(*ent) {
+                    Ok(macc_id) => sim_command_queue.push(SimCommand::MaccSelect(macc_id.0)),
+                    Err(err) => eprintln!("Error querying macc {}", err),
+                }
             },
-            GameEvents::MaccMoveOrder(pos) => {
-                println!("Mac move order");
-                if let Some(selected_macc) = selected_macc.get() {
-                    println!("Selected Macc");
-                    match macc_q.get_mut(selected_macc) {
-                        Ok(mut macc) => {
-                            println!("Setting pos");
-                            macc.target_position = pos.clone();
-                        },
-                        Err(err) => eprintln!("Error querying macc {}", err),
-                    }
+            GameEvents::MaccMoveOrder(pos) => sim_command_queue.push(SimCommand::MaccMoveOrder((pos.x, pos.y))),
+            GameEvents::MaccQueueWaypoint(pos) => sim_command_queue.push(SimCommand::MaccQueueWaypoint((pos.x, pos.y))),
+            GameEvents::MaccOrder(ent, order) => {
+                match macc_q.get(*ent) {
+                    Ok(macc_id) => sim_command_queue.push(SimCommand::MaccOrder(macc_id.0, *order)),
+                    Err(err) => eprintln!("Error querying macc {}", err),
                 }
+            },
+            GameEvents::BuildStructure(pos, kind) => sim_command_queue.push(SimCommand::BuildStructure(*pos, *kind))
+        }
+    }
+}
+
+type StructureSiteQuery<'a> = (Entity, &'a GridPos, Option<&'a Emitter>, Option<&'a Scrubber>, Option<&'a mut Barrier>);
+
+pub fn structure_event_react(
+    mut commands: Commands,
+    mut sim_commands: EventReader<SimCommand>,
+    mut build_funds: ResMut<BuildFunds>,
+    mut hex_grid: ResMut<HexGrid>,
+    weather: Res<Weather>,
+    mut site_q: Query<StructureSiteQuery>
+) {
+    for command in sim_commands.read() {
+        if let SimCommand::BuildStructure(pos, kind) = command {
+            if hex_grid.get(*pos).is_none() {
+                eprintln!("No hex at {:?} to build on", pos);
+                continue;
+            }
+            let grid_pos = GridPos { pos: *pos };
+            let hex_pos = grid_pos.to_int();
+            // Barriers go up on the edge facing most into the wind that they don't wall yet
+            let barrier_edges = site_q.iter()
+                .find(|(_, grid_pos, ..)| grid_pos.pos == *pos)
+                .and_then(|(.., barrier)| barrier)
+                .map_or(0, |barrier| barrier.edges.len());
+            let upwind_edge = hex_grid.layout.directions_by_angle(weather.wind_direction + 180.0)[barrier_edges.min(5)];
+            let previous_edge = edge_before_barriers(
+                &hex_grid,
+                site_q.iter().filter_map(|(_, grid_pos, .., barrier)| barrier.map(|barrier| (grid_pos, barrier))),
+                hex_pos,
+                upwind_edge
+            );
+            // All structures on a hex live on one entity
+            let (site_ent, has_emitter, has_scrubber, barrier) = match site_q.iter_mut().find(|(_, grid_pos, ..)| grid_pos.pos == *pos) {
+                Some((ent, _, emitter, scrubber, barrier)) => (Some(ent), emitter.is_some(), scrubber.is_some(), barrier),
+                None => (None, false, false, None)
+            };
+            let already_built = match kind {
+                StructureKind::Emitter => has_emitter,
+                StructureKind::Scrubber => has_scrubber,
+                StructureKind::Barrier => barrier_edges >= 6,
+            };
+            if already_built || !build_funds.try_spend(kind.cost()) {
+                continue;
+            }
+
+            let site_ent = site_ent.unwrap_or_else(|| commands.spawn(grid_pos).id());
+            match kind {
+                StructureKind::Emitter => {
+                    commands.entity(site_ent).insert(Emitter::default());
+                },
+                StructureKind::Scrubber => {
+                    commands.entity(site_ent).insert(Scrubber::default());
+                },
+                StructureKind::Barrier => {
+                    hex_grid.set_edge(hex_pos, upwind_edge, EdgeAttribute::Wall);
+                    match barrier {
+                        Some(mut barrier) => barrier.edges.push((upwind_edge, previous_edge)),
+                        None => {
+                            commands.entity(site_ent).insert(Barrier { edges: vec![(upwind_edge, previous_edge)] });
+                        }
+                    }
+                },
             }
         }
     }
 }
 
-pub fn map_state_material_static(
+// What an edge was before any barrier walled it. Neighboring barriers can share an edge,
+// the first to wall it remembers what the player had there
+fn edge_before_barriers<'a>(
+    hex_grid: &HexGrid,
+    barriers: impl IntoIterator<Item = (&'a GridPos, &'a Barrier)>,
+    pos: (i32, i32),
+    direction: HexDirection
+) -> EdgeAttribute {
+    let key = hex_grid.edge_key(pos, direction);
+    barriers.into_iter()
+        .flat_map(|(grid_pos, barrier)| barrier.edges.iter().map(move |edge| (grid_pos.to_int(), edge)))
+        .find(|(barrier_pos, (held, _))| hex_grid.edge_key(*barrier_pos, *held) == key)
+        .map_or_else(|| hex_grid.get_edge(pos, direction), |(_, (_, before))| *before)
+}
+
+// Turns barriers with the wind so they keep walling off the edges facing most into it.
+// Edges no barrier wants anymore get back what they were before, unless the player
+// changed them since
+pub fn barrier_track_wind(
+    mut hex_grid: ResMut<HexGrid>,
+    weather: Res<Weather>,
+    mut barrier_q: Query<(&GridPos, &mut Barrier)>
+) {
+    let upwind = hex_grid.layout.directions_by_angle(weather.wind_direction + 180.0);
+    let turned = barrier_q.iter().any(|(_, barrier)| {
+        barrier.edges.iter().map(|(direction, _)| *direction).ne(upwind[..barrier.edges.len().min(6)].iter().copied())
+    });
+    if !turned {
+        return;
+    }
+
+    let grid = &*hex_grid;
+    let mut before: HashMap<EdgeKey, EdgeAttribute> = barrier_q.iter()
+        .flat_map(|(grid_pos, barrier)| {
+            barrier.edges.iter().map(move |(direction, before)| (grid.edge_key(grid_pos.to_int(), *direction), *before))
+        })
+        .collect();
+    let wanted: HashSet<EdgeKey> = barrier_q.iter()
+        .flat_map(|(grid_pos, barrier)| {
+            upwind[..barrier.edges.len().min(6)].iter().map(move |direction| grid.edge_key(grid_pos.to_int(), *direction))
+        })
+        .collect();
+
+    for (key, attribute) in &before {
+        if !wanted.contains(key) && hex_grid.edges.get(key) == Some(&EdgeAttribute::Wall) {
+            hex_grid.set_edge(key.0, key.1, *attribute);
+        }
+    }
+    for (grid_pos, mut barrier) in barrier_q.iter_mut() {
+        let pos = grid_pos.to_int();
+        let count = barrier.edges.len().min(6);
+        barrier.edges = upwind[..count].iter()
+            .map(|direction| {
+                let previous = *before.entry(hex_grid.edge_key(pos, *direction)).or_insert_with(|| hex_grid.get_edge(pos, *direction));
+                hex_grid.set_edge(pos, *direction, EdgeAttribute::Wall);
+                (*direction, previous)
+            })
+            .collect();
+    }
+}
+
+type StructureFilter = Or<(With<Emitter>, With<Scrubber>)>;
+
+pub fn draw_structures(
+    mut gizmos: Gizmos,
+    hex_grid: Res<HexGrid>,
+    structure_q: Query<(&GridPos, Option<&Emitter>, Option<&Scrubber>), StructureFilter>
+) {
+    for (grid_pos, emitter, scrubber) in structure_q.iter() {
+        let center = hex_grid.layout.pos_to_world(grid_pos.to_int());
+        if emitter.is_some() {
+            gizmos.circle_2d(center + Vec2::new(-15.0, 0.0), 8.0, Color::ORANGE_RED);
+        }
+        if let Some(scrubber) = scrubber {
+            gizmos.circle_2d(center + Vec2::new(15.0, 0.0), 8.0, Color::LIME_GREEN);
+            gizmos.circle_2d(center, hex_grid.layout.radius * (2.0 * scrubber.radius as f32 + 1.0) * 0.866, Color::LIME_GREEN);
+        }
+    }
+}
+
+// Around the MACC's sprite
+const SELECTION_RING_RADIUS: f32 = 10.0;
+// How long the marker of a fresh order takes to shrink away
+const ORDER_MARKER_TIME: f32 = 0.6;
+
+// Outline of the selected hex, a ring around the selected MACC and the path it is going to
+// take through its target and queued waypoints
+pub fn draw_selection(
+    mut gizmos: Gizmos,
     map_state: Res<MapState>,
     hex_grid: Res<HexGrid>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    hex_q: Query<(&Children, &Terrain)>,
-    material_q: Query<&mut Handle<ColorMaterial>>
-) {
-    match *map_state {
-        MapState::Terrain => {
-            for row in hex_grid.grid.iter() {
-                for ent in row.iter() {
-                    match hex_q.get(*ent) {
-                        Ok((children, terrain)) => {
-                            children.iter().for_each(|child| {
-                                match material_q.get(*child) {
-                                    Ok(handle) => {
-                                        materials.get_mut(handle).unwrap().color = Color::from(terrain);
-                                    },
-                                    Err(err) => eprintln!("error in map_state_material_static\n{}", err),
-                                }
-                            })
-                        },
-                        Err(err) => eprintln!("error in map_state_material_static\n{}", err),
-                    }
-                }
+    selected_macc: Res<SelectedMacc>,
+    macc_q: Query<(&Macc, &Transform)>
+) {
+    let color = map_state.highlight_color();
+    if let Some(pos) = hex_grid.get_selected() {
+        let pos = (pos.0 as i32, pos.1 as i32);
+        let center = hex_grid.layout.pos_to_world(pos);
+        for direction in hex_grid.layout.directions() {
+            let (start, end) = hex_grid.layout.edge_segment(pos, &direction);
+            gizmos.line_2d(start, end, color);
+            // Doubled a little inside so it reads over the edge lines
+            gizmos.line_2d(center.lerp(start, 0.92), center.lerp(end, 0.92), color);
+        }
+    }
+
+    let Some((macc, trans)) = selected_macc.get().and_then(|ent| macc_q.get(ent).ok()) else {
+        return;
+    };
+    let position = trans.translation.truncate();
+    gizmos.circle_2d(position, SELECTION_RING_RADIUS, color);
+    if macc.in_position(macc.position) && macc.waypoints.is_empty() {
+        return;
+    }
+
+    let path_color = color.with_a(0.6);
+    let mut from = position;
+    for point in std::iter::once(macc.target_position).chain(macc.waypoints.iter().copied()) {
+        gizmos.line_2d(from, point, path_color);
+        gizmos.circle_2d(point, 3.0, path_color);
+        from = point;
+    }
+    // Cross on where it is headed now
+    let arm = Vec2::splat(5.0);
+    gizmos.line_2d(macc.target_position - arm, macc.target_position + arm, color);
+    gizmos.line_2d(macc.target_position + Vec2::new(-arm.x, arm.y), macc.target_position + Vec2::new(arm.x, -arm.y), color);
+}
+
+// A ring closing in on each point a move order or waypoint was just given for
+pub fn draw_order_markers(
+    mut gizmos: Gizmos,
+    time: Res<Time<Real>>,
+    map_state: Res<MapState>,
+    selected_macc: Res<SelectedMacc>,
+    mut game_events: EventReader<GameEvents>,
+    mut markers: Local<Vec<(Vec2, f32)>>
+) {
+    let now = time.elapsed_seconds();
+    for event in game_events.read() {
+        match event {
+            GameEvents::MaccMoveOrder(pos) | GameEvents::MaccQueueWaypoint(pos) if selected_macc.get().is_some() => markers.push((*pos, now)),
+            _ => {}
+        }
+    }
+    markers.retain(|(_, issued)| now - issued < ORDER_MARKER_TIME);
+
+    let color = map_state.highlight_color();
+    for (pos, issued) in markers.iter() {
+        let remaining = 1.0 - (now - issued) / ORDER_MARKER_TIME;
+        gizmos.circle_2d(*pos, 3.0 + 12.0 * remaining, color.with_a(remaining));
+    }
+}
+
+pub fn draw_edges(
+    mut gizmos: Gizmos,
+    hex_grid: Res<HexGrid>
+) {
+    for ((pos, direction), attribute) in hex_grid.edges.iter() {
+        let (start, end) = hex_grid.layout.edge_segment(*pos, direction);
+        let color = match attribute {
+            EdgeAttribute::Open => continue,
+            EdgeAttribute::Wall => Color::YELLOW,
+            EdgeAttribute::Filter(_) => Color::ORANGE,
+        };
+        gizmos.line_2d(start, end, color);
+    }
+}
+
+pub fn draw_boundaries(
+    mut gizmos: Gizmos,
+    hex_grid: Res<HexGrid>
+) {
+    for pos in hex_grid.hexes() {
+        let grid_pos = GridPos { pos };
+        for direction in hex_grid.layout.directions() {
+            let color = match hex_grid.crossed_boundary(&grid_pos, direction) {
+                None => continue,
+                Some(BoundaryKind::Absorbing) => Color::RED,
+                Some(BoundaryKind::Reflective) => Color::WHITE,
+                Some(BoundaryKind::Periodic) => Color::CYAN,
+                Some(BoundaryKind::Reservoir) => Color::PURPLE,
+            };
+            let (start, end) = hex_grid.layout.edge_segment(grid_pos.to_int(), &direction);
+            gizmos.line_2d(start, end, color);
+        }
+    }
+}
+
+// Spawns render entities for the chunks in view of the camera and drops the rest
+pub fn update_visible_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    hex_grid: Res<HexGrid>,
+    nanite_field: Res<NaniteField>,
+    asset_handles: Res<AssetHandles>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    chunk_q: Query<(Entity, &ChunkRender)>
+) {
+    let (camera_trans, projection) = match camera_q.get_single() {
+        Ok(camera) => camera,
+        Err(err) => {
+            eprintln!("Error querying main camera {}", err);
+            return;
+        }
+    };
+    let view = Rect {
+        min: projection.area.min + camera_trans.translation.truncate(),
+        max: projection.area.max + camera_trans.translation.truncate()
+    };
+
+    let visible: HashSet<(usize, usize)> = (0..nanite_field.chunk_dims.0)
+        .flat_map(|row| (0..nanite_field.chunk_dims.1).map(move |col| (row, col)))
+        .filter(|chunk| !chunk_bounds(&hex_grid.layout, *chunk).intersect(view).is_empty())
+        .collect();
+
+    let mut shown: HashSet<(usize, usize)> = HashSet::new();
+    for (ent, chunk_render) in chunk_q.iter() {
+        if visible.contains(&chunk_render.chunk) {
+            shown.insert(chunk_render.chunk);
+        } else {
+            commands.entity(ent).despawn();
+        }
+    }
+
+    for chunk in visible.difference(&shown) {
+        let mesh = chunk_mesh(&hex_grid, &nanite_field, *chunk);
+        commands.spawn((MaterialMesh2dBundle {
+            mesh: meshes.add(mesh).into(),
+            material: asset_handles.get_color_handle_white(),
+            ..default()
+        }, ChunkRender { chunk: *chunk }));
+    }
+}
+
+// Recolors every chunk when the field or map state changed, otherwise just the new ones
+pub fn update_chunk_colors(
+    mut meshes: ResMut<Assets<Mesh>>,
+    nanite_field: Res<NaniteField>,
+    nanite_flow: Res<NaniteFlow>,
+    field_history: Res<FieldHistory>,
+    (map_state, heatmap_settings): (Res<MapState>, Res<HeatmapSettings>),
+    chunk_q: Query<(Ref<ChunkRender>, &Mesh2dHandle)>
+) {
+    let refresh_all = nanite_field.is_changed() || field_history.is_changed() || map_state.is_changed() || heatmap_settings.is_changed();
+    let map_view = MapView::new(&nanite_field, &nanite_flow, &field_history, &map_state, &heatmap_settings);
+    for (chunk_render, mesh_handle) in chunk_q.iter() {
+        if !refresh_all && !chunk_render.is_added() {
+            continue;
+        }
+        match meshes.get_mut(&mesh_handle.0) {
+            Some(mesh) => mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, map_view.chunk_colors(chunk_render.chunk)),
+            None => eprintln!("No mesh for chunk {:?}", chunk_render.chunk),
+        }
+    }
+}
+
+// Redraws the minimap image with the same colors as the chunks, setting it up first
+// whenever a new map is spawned
+pub fn update_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    (hex_grid, nanite_field, nanite_flow, field_history): (Res<HexGrid>, Res<NaniteField>, Res<NaniteFlow>, Res<FieldHistory>),
+    (map_state, heatmap_settings): (Res<MapState>, Res<HeatmapSettings>),
+    minimap_layout: Option<Res<MinimapLayout>>,
+    mut minimap_q: Query<(&mut UiImage, &mut Style), With<Minimap>>
+) {
+    let Ok((mut ui_image, mut style)) = minimap_q.get_single_mut() else {
+        return;
+    };
+    let minimap_layout = match minimap_layout {
+        Some(minimap_layout) if !hex_grid.is_added() => minimap_layout,
+        _ => {
+            let minimap_layout = MinimapLayout::new(&hex_grid);
+            ui_image.texture = images.add(Image::new_fill(
+                Extent3d { width: minimap_layout.size.x, height: minimap_layout.size.y, depth_or_array_layers: 1 },
+                TextureDimension::D2,
+                &[0, 0, 0, 255],
+                TextureFormat::Rgba8UnormSrgb
+            ));
+            style.width = Val::Px(minimap_layout.size.x as f32);
+            style.height = Val::Px(minimap_layout.size.y as f32);
+            commands.insert_resource(minimap_layout);
+            return;
+        }
+    };
+    let changed = nanite_field.is_changed() || field_history.is_changed() || map_state.is_changed() || heatmap_settings.is_changed();
+    if !changed && !minimap_layout.is_added() {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&ui_image.texture) else {
+        return;
+    };
+    let map_view = MapView::new(&nanite_field, &nanite_flow, &field_history, &map_state, &heatmap_settings);
+    for (pixel, pos) in image.data.chunks_exact_mut(4).zip(&minimap_layout.pixels) {
+        let color = pos.map_or(Color::BLACK, |pos| map_view.hex_color(pos));
+        pixel.copy_from_slice(&color.as_rgba_u8());
+    }
+}
+
+// World space bounds of a chunk, padded so the half hex offsets fit
+fn chunk_bounds(layout: &HexLayout, chunk: (usize, usize)) -> Rect {
+    let first = ((chunk.0 * CHUNK_SIZE) as i32, (chunk.1 * CHUNK_SIZE) as i32);
+    let last = (first.0 + CHUNK_SIZE as i32 - 1, first.1 + CHUNK_SIZE as i32 - 1);
+    Rect::from_corners(layout.pos_to_world(first), layout.pos_to_world(last)).inset(layout.radius * 2.0)
+}
+
+// A white hex with a slightly smaller one on top in the map state color,
+// leaving an outline. Colors are filled in by update_chunk_colors
+fn chunk_mesh(hex_grid: &HexGrid, nanite_field: &NaniteField, chunk: (usize, usize)) -> Mesh {
+    let layout = &hex_grid.layout;
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for pos in nanite_field.chunk_positions(chunk) {
+        let center = layout.pos_to_world((pos.0 as i32, pos.1 as i32));
+        for (radius, z) in [(layout.radius, 0.0), (layout.radius - 1.0, 1.0)] {
+            let first = positions.len() as u32;
+            positions.push(center.extend(z).to_array());
+            positions.extend(layout.corners(radius).map(|corner| (center + corner).extend(z).to_array()));
+            for corner in 0..6 {
+                indices.extend([first, first + 1 + corner, first + 1 + (corner + 1) % 6]);
             }
-        },
-        _ => {} // Nanite taken care of in nanite_material_update
-    }
-}
-
-pub fn nanite_material_update(
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    nanite_q: Query<(&Children, &Nanite), Changed<Nanite>>,
-    material_q: Query<&mut Handle<ColorMaterial>>
-) {
-    for (children, nanite) in nanite_q.iter() {
-        children.iter().for_each(|child| {
-            match material_q.get(*child) {
-                Ok(handle) => {
-                    let col = if nanite.nanite_total > 0.0 {
-                        Color::rgba(
-                            0.25, 
-                            0.42, 
-                            0.85,
-                            (nanite.nanite_total as f32 / nanite.nanite_capacity as f32).clamp(0.1, 1.0)
-                        )
-                    } else {
-                        Color::GRAY
-                    };
-                    materials.get_mut(handle).unwrap().color = col;
-                },
-                Err(_) => {
-                    eprintln!("No material component on nanite's child")
-                }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// What the map overlays read, the viewed history entry stands in for the field when looking back
+struct MapView<'a> {
+    nanite_field: &'a NaniteField,
+    nanite_flow: &'a NaniteFlow,
+    viewed: Option<&'a HistoryEntry>,
+    // The tick before the one shown, for rates
+    previous: Option<&'a HistoryEntry>,
+    map_state: &'a MapState,
+    heatmap_settings: &'a HeatmapSettings
+}
+
+impl<'a> MapView<'a> {
+    fn new(nanite_field: &'a NaniteField, nanite_flow: &'a NaniteFlow, field_history: &'a FieldHistory, map_state: &'a MapState, heatmap_settings: &'a HeatmapSettings) -> Self {
+        let shown = field_history.viewing().or(field_history.range().map(|(_, last)| last));
+        Self {
+            nanite_field,
+            nanite_flow,
+            viewed: field_history.viewed(),
+            previous: shown.and_then(|tick| field_history.get(tick.checked_sub(1)?)),
+            map_state,
+            heatmap_settings
+        }
+    }
+
+    fn nanite(&self, pos: (usize, usize)) -> Option<&Nanite> {
+        match self.viewed {
+            Some(entry) => entry.nanite(pos),
+            None => self.nanite_field.nanite(pos)
+        }
+    }
+
+    fn flow(&self, pos: (usize, usize)) -> f32 {
+        match self.viewed {
+            Some(entry) => entry.flow(pos),
+            None => self.nanite_flow.net(pos.0 * self.nanite_field.dimensions.1 + pos.1)
+        }
+    }
+
+    // Change in the nanite total since the tick before, none before the second tick
+    fn rate(&self, pos: (usize, usize)) -> Option<f32> {
+        let before = self.previous?.nanite(pos)?.nanite_total;
+        Some(self.nanite(pos)?.nanite_total - before)
+    }
+
+    fn hex_color(&self, pos: (usize, usize)) -> Color {
+        let (Some(terrain), Some(nanite)) = (self.nanite_field.terrain(pos), self.nanite(pos)) else {
+            return Color::GRAY;
+        };
+        match self.map_state {
+            MapState::Terrain => Color::from(terrain),
+            MapState::Nanite => self.heatmap_settings.color(nanite.nanite_total),
+            MapState::Flux => self.heatmap_settings.signed_color(self.flow(pos), HeatmapSettings::RATE_RANGE),
+            MapState::RateOfChange => self.heatmap_settings.signed_color(self.rate(pos).unwrap_or(0.0), HeatmapSettings::RATE_RANGE),
+            // Faded so the arrows drawn by draw_wind_field stand out
+            MapState::Wind => {
+                let color = Color::from(terrain);
+                Color::rgb(color.r() * 0.4, color.g() * 0.4, color.b() * 0.4)
             }
-        });
+            MapState::Saturation => {
+                let ticks = match self.rate(pos) {
+                    _ if nanite.is_full() => Some(0.0),
+                    Some(rate) if rate > 0.0 => Some((nanite.nanite_capacity - nanite.nanite_total) / rate),
+                    _ => None
+                };
+                self.heatmap_settings.saturation_color(ticks)
+            }
+            MapState::Danger => danger_color(nanite.nanite_total / nanite.nanite_capacity),
+            MapState::Blend => self.heatmap_settings.blend_color(Color::from(terrain), nanite.nanite_total),
+        }
+    }
+
+    // Vertex colors in the same order as chunk_mesh
+    fn chunk_colors(&self, chunk: (usize, usize)) -> Vec<[f32; 4]> {
+        self.nanite_field.chunk_positions(chunk)
+            .flat_map(|pos| {
+                let color = self.hex_color(pos);
+                [Color::WHITE.as_linear_rgba_f32(); 7].into_iter()
+                    .chain([color.as_linear_rgba_f32(); 7])
+            })
+            .collect()
+    }
+}
+
+// An arrow over every hex in view pointing where the wind carries its nanites,
+// shorter where walls and filters hold them back
+pub fn draw_wind_field(
+    mut gizmos: Gizmos,
+    hex_grid: Res<HexGrid>,
+    weather: Res<Weather>,
+    field_history: Res<FieldHistory>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>
+) {
+    let Ok((camera_trans, projection)) = camera_q.get_single() else {
+        return;
+    };
+    let weather = field_history.viewed().map_or(&*weather, |entry| &entry.weather);
+    let wind_direction = hex_grid.layout.direction_from_angle(weather.wind_direction);
+    // Along the hex direction the nanites actually move in
+    let heading = Vec2::from_angle(hex_grid.layout.edge_angle(wind_direction).to_radians());
+    let view = Rect {
+        min: projection.area.min + camera_trans.translation.truncate(),
+        max: projection.area.max + camera_trans.translation.truncate()
+    };
+    for pos in hex_grid.hexes_in_rect(view) {
+        let permeability = hex_grid.edge_permeability(&GridPos { pos }, wind_direction);
+        let length = hex_grid.layout.radius * 0.8 * (weather.wind_strength * permeability).clamp(0.0, 1.0);
+        if length <= 0.0 {
+            continue;
+        }
+        let center = hex_grid.layout.pos_to_world((pos.0 as i32, pos.1 as i32));
+        let (start, end) = (center - heading * length / 2.0, center + heading * length / 2.0);
+        gizmos.line_2d(start, end, Color::WHITE);
+        for side in [-1.0, 1.0] {
+            let barb = Vec2::from_angle(side * 2.6).rotate(heading) * length * 0.3;
+            gizmos.line_2d(end, end + barb, Color::WHITE);
+        }
+    }
+}
+
+// Arrows over the edges nanites crossed on the last tick, drawn with more strokes the more
+// crossed, and a dot running along each towards where the nanites went
+pub fn draw_nanite_flow(
+    mut gizmos: Gizmos,
+    time: Res<Time<Real>>,
+    hex_grid: Res<HexGrid>,
+    nanite_flow: Res<NaniteFlow>,
+    flow_overlay: Res<FlowOverlay>,
+    field_history: Res<FieldHistory>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>
+) {
+    // Edge flows aren't kept in the history
+    if field_history.viewing().is_some() {
+        return;
+    }
+    let Ok((camera_trans, projection)) = camera_q.get_single() else {
+        return;
+    };
+    let layout = &hex_grid.layout;
+    let view = Rect {
+        min: projection.area.min + camera_trans.translation.truncate(),
+        max: projection.area.max + camera_trans.translation.truncate()
+    }.inset(layout.radius * 2.0);
+
+    for (shown, edges, color) in [
+        (flow_overlay.wind, &nanite_flow.wind_edges, Color::CYAN),
+        (flow_overlay.dispersion, &nanite_flow.dispersion_edges, Color::ORANGE)
+    ] {
+        if !shown {
+            continue;
+        }
+        for edge in edges.iter().filter(|edge| edge.amount >= FlowOverlay::MIN_AMOUNT) {
+            let pos = (edge.pos.0 as i32, edge.pos.1 as i32);
+            let center = layout.pos_to_world(pos);
+            if !view.contains(center) {
+                continue;
+            }
+            // From near the hex center to near its neighbor's, twice the way to the shared edge
+            let (corner_a, corner_b) = layout.edge_segment(pos, &edge.direction);
+            let to_edge = (corner_a + corner_b) / 2.0 - center;
+            let (start, end) = (center + to_edge * 0.3, center + to_edge * 1.7);
+            let heading = to_edge.normalize();
+
+            let strokes = flow_overlay.thickness(edge.amount);
+            for stroke in 0..strokes {
+                let offset = heading.perp() * (stroke as f32 - (strokes - 1) as f32 / 2.0);
+                gizmos.line_2d(start + offset, end + offset, color);
+            }
+            for side in [-1.0, 1.0] {
+                let barb = Vec2::from_angle(side * 2.6).rotate(heading) * to_edge.length() * 0.4;
+                gizmos.line_2d(end, end + barb, color);
+            }
+
+            // Bigger flows run faster, neighbors start out of step
+            let speed = 0.5 + (edge.amount / flow_overlay.max_amount).clamp(0.0, 1.0);
+            let phase = (time.elapsed_seconds() * speed + (edge.pos.0 * 7 + edge.pos.1 * 13) as f32 * 0.1).fract();
+            gizmos.circle_2d(start.lerp(end, phase), strokes as f32 / 2.0 + 1.0, color);
+        }
+    }
+}
+
+// Writes the nanite total over every hex in view while zoomed in, the labels
+// come and go with the camera like the chunks
+pub fn update_value_labels(
+    mut commands: Commands,
+    hex_grid: Res<HexGrid>,
+    nanite_field: Res<NaniteField>,
+    field_history: Res<FieldHistory>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut label_q: Query<(Entity, &HexValueLabel, &mut Text)>
+) {
+    let (camera_trans, projection) = match camera_q.get_single() {
+        Ok(camera) => camera,
+        Err(err) => {
+            eprintln!("Error querying main camera {}", err);
+            return;
+        }
+    };
+    let visible: HashSet<(usize, usize)> = if projection.scale <= HeatmapSettings::VALUE_ZOOM {
+        hex_grid.hexes_in_rect(Rect {
+            min: projection.area.min + camera_trans.translation.truncate(),
+            max: projection.area.max + camera_trans.translation.truncate()
+        }).collect()
+    } else {
+        HashSet::new()
+    };
+    let viewed = field_history.viewed();
+    let value = |pos: (usize, usize)| {
+        let nanite = match viewed {
+            Some(entry) => entry.nanite(pos),
+            None => nanite_field.nanite(pos)
+        };
+        format!("{:.1}", nanite.map_or(0.0, |nanite| nanite.nanite_total))
+    };
+
+    let mut shown: HashSet<(usize, usize)> = HashSet::new();
+    for (ent, label, mut text) in label_q.iter_mut() {
+        if !visible.contains(&label.pos) {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        shown.insert(label.pos);
+        let value = value(label.pos);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+
+    for pos in visible.difference(&shown) {
+        let center = hex_grid.layout.pos_to_world((pos.0 as i32, pos.1 as i32));
+        commands.spawn((Text2dBundle {
+            text: Text::from_section(value(*pos), TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            }),
+            transform: Transform::from_translation(center.extend(2.0)),
+            ..default()
+        }, HexValueLabel { pos: *pos }));
+    }
+}
+
+pub fn clear_value_labels(
+    mut commands: Commands,
+    label_q: Query<Entity, With<HexValueLabel>>
+) {
+    for ent in label_q.iter() {
+        commands.entity(ent).despawn();
     }
 }
 
+// Steps every MACC towards its target in the SimTick so replays move them the same way
 pub fn move_maccs(
+    sim_clock: Res<SimClock>,
+    mut macc_q: Query<(&mut Macc, Option<&MaccStatus>)>
+) {
+    for (mut macc, status) in macc_q.iter_mut() {
+        macc.speed = 0.0;
+        if status.is_some_and(|status| status.disabled()) {
+            continue;
+        }
+        let start = macc.position;
+        for _ in 0..Macc::STEPS_PER_TICK {
+            if macc.in_position(macc.position) {
+                // On to the next waypoint
+                if !macc.waypoints.is_empty() && !macc.hold {
+                    macc.target_position = macc.waypoints.remove(0);
+                }
+                continue;
+            }
+            // Need to rotate?
+            let direction_vec = (macc.target_position - macc.position).normalize();
+            let forward_vec = Vec2::from_angle(macc.heading).rotate(Vec2::Y);
+            let cross_prod = direction_vec.perp_dot(forward_vec);
+            let angle = direction_vec.angle_between(forward_vec).abs();
+            let angle_delta = angle.min(macc.turn_radius.to_radians());
+            macc.heading += angle_delta * -cross_prod.signum();
+
+            if !(-0.1..0.1).contains(&cross_prod) {
+                continue;
+            }
+            //Move forward
+            let step = macc.max_step.min(macc.target_position.distance(macc.position));
+            let forward_vec = Vec2::from_angle(macc.heading).rotate(Vec2::Y);
+            macc.position += forward_vec * step;
+        }
+        macc.speed = macc.position.distance(start) / sim_clock.tick_length;
+    }
+}
+
+// Eases the sprites towards where the last tick left their MACCs, snapping at max speed
+pub fn ease_macc_transforms(
+    time: Res<Time>,
+    sim_clock: Res<SimClock>,
     mut macc_q: Query<(&mut Transform, &Macc)>
 ) {
+    let t = match sim_clock.speed {
+        SimSpeed::Max => 1.0,
+        _ => (time.delta_seconds() * 4.0 / sim_clock.tick_length).min(1.0)
+    };
     for (mut trans, macc) in macc_q.iter_mut() {
-        if macc.in_position(trans.translation.truncate()) {
-            continue;
+        let position = trans.translation.truncate().lerp(macc.position, t);
+        trans.translation = position.extend(trans.translation.z);
+        trans.rotation = trans.rotation.slerp(Quat::from_rotation_z(macc.heading), t);
+    }
+}
+
+// MACCs wear down and gather samples on contaminated hexes, and unload at home
+pub fn macc_exposure(
+    hex_grid: Res<HexGrid>,
+    nanite_field: Res<NaniteField>,
+    mut macc_q: Query<(&Macc, &mut MaccStatus)>
+) {
+    for (macc, mut status) in macc_q.iter_mut() {
+        status.expose(exposure_at(&hex_grid, &nanite_field, macc.position));
+        if macc.home_position.distance(macc.position) <= 1.0 {
+            status.cargo = 0.0;
         }
-        // Need to rotate?
-        let direction_vec = (macc.target_position - trans.translation.truncate()).normalize();
-        let forward_vec = trans.up().truncate().normalize();
-        let cross_prod = direction_vec.perp_dot(forward_vec);
-        let angle = direction_vec.angle_between(forward_vec).abs();
-        let angle_delta = angle.min(macc.turn_radius.to_radians());
-        trans.rotate_z(angle_delta * -cross_prod.signum());
+    }
+}
 
-        if !(-0.1..0.1).contains(&cross_prod) {
-            continue;
+// Nanites on the hex under a world position, 0 off the map
+fn exposure_at(hex_grid: &HexGrid, nanite_field: &NaniteField, position: Vec2) -> f32 {
+    hex_grid.get_at_world(position)
+        .and_then(|pos| nanite_field.nanite(pos))
+        .map_or(0.0, |nanite| nanite.nanite_total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::change_detection::Mut;
+    use crate::components::terrain::Terrain;
+
+    // Rectangle with every seventh hex left out, big enough to span several batches
+    fn holed_grid(rows: usize, cols: usize) -> HexGrid {
+        let positions: Vec<(usize, usize)> = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .filter(|(row, col)| (row * cols + col) % 7 != 3)
+            .collect();
+        HexGrid::new(&positions, HexLayout::default())
+    }
+
+    #[test]
+    fn par_map_hexes_matches_serial_map() {
+        let hex_grid = holed_grid(60, 60);
+        assert!(hex_grid.hexes().count() > 2 * PAR_BATCH_SIZE);
+        let f = |(row, col): (usize, usize)| row * 1000 + col + 1;
+
+        let mut serial = vec![0; hex_grid.cells.len()];
+        for pos in hex_grid.hexes() {
+            serial[hex_grid.index(pos)] = f(pos);
+        }
+        assert_eq!(par_map_hexes(&hex_grid, f), serial);
+    }
+
+    // Holed grid with an absorbing and a reflective map edge, walls and filters inside
+    // and nanites up to twice the capacity so full hexes overflow
+    fn wind_dispersion_world(mode: DispersionMode) -> World {
+        let mut hex_grid = holed_grid(60, 60);
+        hex_grid.boundaries.left = BoundaryKind::Absorbing;
+        hex_grid.boundaries.top = BoundaryKind::Reflective;
+        for (index, pos) in hex_grid.hexes().collect::<Vec<_>>().into_iter().enumerate().step_by(11) {
+            let direction = hex_grid.layout.directions()[index % 6];
+            let attribute = if index % 2 == 0 { EdgeAttribute::Wall } else { EdgeAttribute::Filter(0.25) };
+            hex_grid.set_edge((pos.0 as i32, pos.1 as i32), direction, attribute);
         }
-        //Move forward
-        trans.translation = trans.translation + (trans.up() * 1.0_f32.min(macc.target_position.distance(trans.translation.truncate())));
+
+        let mut rng = SimRng::new(11);
+        let mut nanite_field = NaniteField::new(hex_grid.dimensions, hex_grid.hexes().map(|pos| (pos, Terrain::from_random(&mut rng))));
+        for pos in hex_grid.hexes() {
+            let nanite = nanite_field.nanite_mut(pos).unwrap();
+            nanite.nanite_total = rng.gen_range(0.0..2.0 * nanite.nanite_capacity);
+        }
+
+        let mut world = World::new();
+        world.insert_resource(hex_grid);
+        world.insert_resource(nanite_field);
+        world.insert_resource(Weather { wind_strength: 0.3, wind_direction: 200.0 });
+        world.insert_resource(NaniteReserve { amount: 0.0 });
+        world.insert_resource(DispersionSettings { mode, coefficient: 0.4, ..default() });
+        world.init_resource::<NaniteFlow>();
+        world
     }
-}
\ No newline at end of file
+
+    // Wind then dispersion one hex after the other in row major order, no task pool involved
+    fn serial_wind_dispersion(world: &mut World) {
+        world.resource_scope(|world, mut nanite_field: Mut<NaniteField>| {
+            let hex_grid = world.resource::<HexGrid>();
+            let weather = world.resource::<Weather>();
+            let dispersion_settings = world.resource::<DispersionSettings>();
+            let mut reserve = world.resource::<NaniteReserve>().amount;
+
+            let wind_direction = hex_grid.layout.direction_from_angle(weather.wind_direction);
+            let before_wind = nanite_field.snapshot();
+            let mut incoming = vec![0.0; before_wind.len()];
+            for pos in hex_grid.hexes() {
+                let grid_pos = GridPos { pos };
+                let strength = weather.wind_strength * hex_grid.edge_permeability(&grid_pos, wind_direction);
+                let downwind = hex_grid.get_neigbors(&grid_pos).some_neighbors().find(|(direction, _)| *direction == wind_direction);
+                let boundary = hex_grid.crossed_boundary(&grid_pos, wind_direction);
+                // Reflective and periodic edges without a hex behind them keep the nanites
+                let held = downwind.is_none() && matches!(boundary, Some(BoundaryKind::Reflective | BoundaryKind::Periodic));
+                if strength <= 0.0 || held {
+                    continue;
+                }
+                let pulled = nanite_field.nanite_mut(pos).unwrap().wind_pull(strength);
+                match downwind {
+                    Some((_, neighbor_pos)) => incoming[hex_grid.index(neighbor_pos)] += before_wind[hex_grid.index(pos)] * strength.clamp(0.0, 1.0),
+                    None if boundary != Some(BoundaryKind::Absorbing) => reserve += pulled,
+                    None => {}
+                }
+            }
+            for pos in hex_grid.hexes() {
+                nanite_field.nanite_mut(pos).unwrap().add_transient_nanites(incoming[hex_grid.index(pos)]);
+            }
+
+            let snapshot = nanite_field.snapshot();
+            let mut links = vec![Vec::new(); snapshot.len()];
+            for pos in hex_grid.hexes() {
+                links[hex_grid.index(pos)] = dispersion_links(hex_grid, &GridPos { pos });
+            }
+            let mut deltas = vec![0.0; snapshot.len()];
+            match dispersion_settings.mode {
+                DispersionMode::Overflow => {
+                    let mut shares = vec![0.0; snapshot.len()];
+                    for pos in hex_grid.hexes() {
+                        let index = hex_grid.index(pos);
+                        let nanite = nanite_field.nanite(pos).unwrap();
+                        let low_neighbors = links[index].iter().filter(|(_, neighbor, _)| overflows_into(&snapshot, index, *neighbor)).count();
+                        if nanite.is_full() && low_neighbors > 0 {
+                            shares[index] = nanite.overflow() / low_neighbors as f32;
+                        }
+                    }
+                    for pos in hex_grid.hexes() {
+                        let index = hex_grid.index(pos);
+                        deltas[index] = links[index].iter()
+                            .map(|(_, neighbor, permeability)| {
+                                let outgoing = if overflows_into(&snapshot, index, *neighbor) { shares[index] } else { 0.0 };
+                                let incoming = neighbor.filter(|neighbor| overflows_into(&snapshot, *neighbor, Some(index))).map_or(0.0, |neighbor| shares[neighbor]);
+                                (incoming - outgoing) * permeability
+                            })
+                            .sum();
+                    }
+                },
+                DispersionMode::Diffusion => {
+                    let substeps = dispersion_settings.substeps();
+                    let rate = dispersion_settings.coefficient * dispersion_settings.timestep / substeps as f32;
+                    let mut totals = snapshot.clone();
+                    for _ in 0..substeps {
+                        let fluxes: Vec<f32> = (0..totals.len())
+                            .map(|index| links[index].iter()
+                                .map(|(_, neighbor, permeability)| rate * permeability * (neighbor.map_or(0.0, |neighbor| totals[neighbor]) - totals[index]))
+                                .sum())
+                            .collect();
+                        for (total, flux) in totals.iter_mut().zip(fluxes) {
+                            *total += flux;
+                        }
+                    }
+                    for (delta, (total, before)) in deltas.iter_mut().zip(totals.iter().zip(&snapshot)) {
+                        *delta = total - before;
+                    }
+                },
+            }
+            for pos in hex_grid.hexes() {
+                nanite_field.nanite_mut(pos).unwrap().add_transient_nanites(deltas[hex_grid.index(pos)]);
+            }
+            world.resource_mut::<NaniteReserve>().amount = reserve;
+        });
+        world.run_system_once(nanite_transient_apply);
+    }
+
+    #[test]
+    fn parallel_wind_dispersion_matches_serial() {
+        for mode in [DispersionMode::Overflow, DispersionMode::Diffusion] {
+            let mut parallel = wind_dispersion_world(mode);
+            assert!(parallel.resource::<HexGrid>().hexes().count() > 2 * PAR_BATCH_SIZE);
+            parallel.run_system_once(nanite_wind);
+            parallel.run_system_once(nanite_dispersion);
+            parallel.run_system_once(nanite_transient_apply);
+
+            let mut serial = wind_dispersion_world(mode);
+            serial_wind_dispersion(&mut serial);
+
+            assert!(!parallel.resource::<NaniteFlow>().wind_edges.is_empty());
+            assert_eq!(parallel.resource::<NaniteField>().snapshot(), serial.resource::<NaniteField>().snapshot(), "{mode:?}");
+            assert_eq!(parallel.resource::<NaniteReserve>().amount, serial.resource::<NaniteReserve>().amount, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn diffusion_conserves_nanites() {
+        // Reservoir edges and holes send nothing off the map, only absorbing ones do
+        let hex_grid = holed_grid(20, 30);
+        let dispersion_settings = DispersionSettings { coefficient: 0.5, ..default() };
+        assert!(dispersion_settings.substeps() > 1);
+
+        let mut rng = SimRng::new(7);
+        let snapshot: Vec<f32> = (0..hex_grid.cells.len()).map(|_| rng.gen_range(0.0..100.0)).collect();
+        let links: Vec<DispersionLinks> = (0..hex_grid.cells.len())
+            .map(|index| (index / hex_grid.dimensions.1, index % hex_grid.dimensions.1))
+            .map(|pos| if hex_grid.get(pos).is_some() { dispersion_links(&hex_grid, &GridPos { pos }) } else { Vec::new() })
+            .collect();
+        let (deltas, link_flows) = diffusion_flows(&hex_grid, &dispersion_settings, &links, &snapshot);
+
+        let total: f32 = snapshot.iter().sum();
+        let moved: f32 = deltas.iter().sum();
+        assert!(moved.abs() < total * 1e-5, "{moved} of {total} appeared or vanished");
+
+        // What a hex sends over an edge is what the hex on the other side receives
+        for pos in hex_grid.hexes() {
+            let index = hex_grid.index(pos);
+            for ((direction, neighbor, _), flow) in links[index].iter().zip(&link_flows[index]) {
+                let neighbor = neighbor.expect("no absorbing edges");
+                let back = links[neighbor].iter().position(|(back, _, _)| *back == direction.opposite()).unwrap();
+                assert!((flow + link_flows[neighbor][back]).abs() < 1e-3, "{pos:?} {direction:?}");
+            }
+        }
+    }
+}
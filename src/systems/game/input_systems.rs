@@ -1,9 +1,9 @@
-use bevy::{ecs::{system::{ResMut, Query, Res}, query::With, event::{EventReader, EventWriter}}, window::{PrimaryWindow, Window}, render::camera::{Camera, OrthographicProjection}, transform::components::{GlobalTransform, Transform}, input::{Input, mouse::{MouseButton, MouseWheel}, keyboard::KeyCode}, math::Vec3, time::Time};
+use bevy::{ecs::{system::{ResMut, Query, Res, Local}, query::{With, Without}, event::{EventReader, EventWriter}}, window::{PrimaryWindow, Window}, render::camera::{Camera, OrthographicProjection}, transform::components::{GlobalTransform, Transform}, input::{Input, mouse::{MouseButton, MouseWheel}, keyboard::KeyCode}, math::Vec2, time::{Time, Real}};
 use bevy_rapier2d::{plugin::RapierContext, pipeline::QueryFilter};
 
-use crate::{resources::input::MouseWorldCoords, components::{clickable::ClickSignal, game_events::GameEvents}};
+use crate::{resources::{input::{MouseWorldCoords, SelectedMacc}, field::NaniteField, history::FieldHistory, dispersion::DispersionSettings, hex::{HexGrid, MapState, EdgeAttribute}, boundary::MapEdge, sim_clock::{SimClock, SimSpeed}, replay::{Replay, SimCommandQueue}, heatmap::HeatmapSettings, flow::FlowOverlay}, components::{clickable::ClickSignal, macc::Macc, game_events::{GameEvents, SimCommand}}};
 
-use super::startup_systems::MainCamera;
+use super::startup_systems::{MainCamera, CameraFocus};
 
 pub fn calc_world_coords(
     mut mouse_wrld_coords: ResMut<MouseWorldCoords>,
@@ -20,60 +20,415 @@ pub fn calc_world_coords(
     }
 }
 
+// Panning moves the same distance on screen whatever the zoom or frame rate
 pub fn keyboard_input(
     keys: Res<Input<KeyCode>>,
-    mut camera_q: Query<(&mut Transform, &MainCamera)>
+    time: Res<Time<Real>>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection, &mut MainCamera)>
 ) {
-    let (mut camera_trans, camera) = camera_q.get_single_mut().unwrap();
+    let (mut camera_trans, ortho_proj, mut camera) = camera_q.get_single_mut().unwrap();
     
     //Movement Input
+    let mut direction = Vec2::ZERO;
     // Forward/Backward
     if keys.pressed(KeyCode::W) {
-        camera_trans.translation += Vec3::Y * camera.translation_speed;
+        direction.y += 1.0;
     } else if keys.pressed(KeyCode::S) {
-        camera_trans.translation -= Vec3::Y * camera.translation_speed;
+        direction.y -= 1.0;
     }
 
     //Left/Right
     if keys.pressed(KeyCode::A) {
-        camera_trans.translation -= Vec3::X * camera.translation_speed;
+        direction.x -= 1.0;
     } else if keys.pressed(KeyCode::D) {
-        camera_trans.translation += Vec3::X * camera.translation_speed;
+        direction.x += 1.0;
     }
 
+    if direction != Vec2::ZERO {
+        camera.stop_focus();
+    }
+    camera_trans.translation += (direction.normalize_or_zero() * camera.translation_speed * ortho_proj.scale * time.delta_seconds()).extend(0.0);
+}
+
+// Holding the cursor against a window edge scrolls that way
+pub fn edge_scroll_camera(
+    time: Res<Time<Real>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection, &mut MainCamera)>
+) {
+    let (Ok(window), Ok((mut camera_trans, ortho_proj, mut camera))) = (window_q.get_single(), camera_q.get_single_mut()) else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position().filter(|_| window.focused && camera.edge_scroll_margin > 0.0) else {
+        return;
+    };
+
+    let mut direction = Vec2::ZERO;
+    if cursor.x <= camera.edge_scroll_margin {
+        direction.x -= 1.0;
+    } else if cursor.x >= window.width() - camera.edge_scroll_margin {
+        direction.x += 1.0;
+    }
+    // Window coordinates run down from the top
+    if cursor.y <= camera.edge_scroll_margin {
+        direction.y += 1.0;
+    } else if cursor.y >= window.height() - camera.edge_scroll_margin {
+        direction.y -= 1.0;
+    }
+
+    if direction != Vec2::ZERO {
+        camera.stop_focus();
+    }
+    camera_trans.translation += (direction.normalize_or_zero() * camera.translation_speed * ortho_proj.scale * time.delta_seconds()).extend(0.0);
+}
+
+// Dragging with the middle mouse button keeps the grabbed point under the cursor
+pub fn drag_pan_camera(
+    mouse_input: Res<Input<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection, &mut MainCamera)>,
+    mut last_cursor: Local<Option<Vec2>>
+) {
+    let (Ok(window), Ok((mut camera_trans, ortho_proj, mut camera))) = (window_q.get_single(), camera_q.get_single_mut()) else {
+        return;
+    };
+    let cursor = window.cursor_position().filter(|_| mouse_input.pressed(MouseButton::Middle));
+    if let (Some(cursor), Some(last)) = (cursor, *last_cursor) {
+        camera.stop_focus();
+        let delta = (cursor - last) * ortho_proj.scale;
+        camera_trans.translation.x -= delta.x;
+        camera_trans.translation.y += delta.y;
+    }
+    *last_cursor = cursor;
+}
+
+pub fn toggle_replication(
+    keys: Res<Input<KeyCode>>,
+    mut sim_command_queue: ResMut<SimCommandQueue>
+) {
+    if keys.just_pressed(KeyCode::R) {
+        sim_command_queue.push(SimCommand::ToggleReplication);
+    }
+}
+
+pub fn dispersion_input(
+    keys: Res<Input<KeyCode>>,
+    dispersion_settings: Res<DispersionSettings>,
+    mut sim_command_queue: ResMut<SimCommandQueue>
+) {
+    if keys.just_pressed(KeyCode::F) {
+        sim_command_queue.push(SimCommand::ToggleDispersionMode);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        sim_command_queue.push(SimCommand::SetDispersionCoefficient(dispersion_settings.coefficient + 0.05));
+    } else if keys.just_pressed(KeyCode::BracketLeft) {
+        sim_command_queue.push(SimCommand::SetDispersionCoefficient((dispersion_settings.coefficient - 0.05).max(0.0)));
+    }
+}
+
+// M steps through the map overlays, backwards with shift, and Alt+1 to Alt+8 pick one
+pub fn map_overlay_input(
+    keys: Res<Input<KeyCode>>,
+    mut map_state: ResMut<MapState>
+) {
+    if keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        let digits = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8];
+        for (key, state) in digits.into_iter().zip(MapState::ALL) {
+            if keys.just_pressed(key) {
+                *map_state = state;
+            }
+        }
+    }
+    if keys.just_pressed(KeyCode::M) {
+        *map_state = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            map_state.previous()
+        } else {
+            map_state.next()
+        };
+    }
+}
+
+// G shows the flow arrows, shift picks wind, dispersion or both
+pub fn flow_overlay_input(
+    keys: Res<Input<KeyCode>>,
+    mut flow_overlay: ResMut<FlowOverlay>
+) {
+    if !keys.just_pressed(KeyCode::G) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        (flow_overlay.wind, flow_overlay.dispersion) = match (flow_overlay.wind, flow_overlay.dispersion) {
+            (true, true) => (true, false),
+            (true, false) => (false, true),
+            _ => (true, true)
+        };
+    } else {
+        flow_overlay.enabled = !flow_overlay.enabled;
+    }
+}
+
+// H cycles the color ramp, L toggles log scaling and V the numbers on each hex
+pub fn heatmap_input(
+    keys: Res<Input<KeyCode>>,
+    mut heatmap_settings: ResMut<HeatmapSettings>
+) {
+    if keys.just_pressed(KeyCode::H) {
+        heatmap_settings.ramp = heatmap_settings.ramp.next();
+    }
+    if keys.just_pressed(KeyCode::L) {
+        heatmap_settings.toggle_scale();
+    }
+    if keys.just_pressed(KeyCode::V) {
+        heatmap_settings.show_values = !heatmap_settings.show_values;
+    }
+}
+
+pub fn sim_clock_input(
+    keys: Res<Input<KeyCode>>,
+    mut sim_clock: ResMut<SimClock>
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        sim_clock.toggle_pause();
+    }
+    if keys.just_pressed(KeyCode::N) {
+        sim_clock.step();
+    }
+    // With Alt the numbers pick map overlays instead
+    if keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        return;
+    }
+    for (key, speed) in [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5].into_iter().zip(SimSpeed::ALL) {
+        if keys.just_pressed(key) {
+            sim_clock.speed = speed;
+        }
+    }
+}
+
+// Scrubbing through a replay, arrows jump 10 ticks and Home goes back to the start
+pub fn replay_input(
+    keys: Res<Input<KeyCode>>,
+    sim_clock: Res<SimClock>,
+    mut replay: ResMut<Replay>
+) {
+    let target = if keys.just_pressed(KeyCode::Left) {
+        sim_clock.tick.saturating_sub(10)
+    } else if keys.just_pressed(KeyCode::Right) {
+        sim_clock.tick + 10
+    } else if keys.just_pressed(KeyCode::Home) {
+        0
+    } else {
+        return;
+    };
+    replay.seek(target, sim_clock.tick);
+}
+
+pub fn boundary_input(
+    keys: Res<Input<KeyCode>>,
+    mut sim_command_queue: ResMut<SimCommandQueue>
+) {
+    for (key, edge) in [
+        (KeyCode::F1, MapEdge::Top),
+        (KeyCode::F2, MapEdge::Right),
+        (KeyCode::F3, MapEdge::Bottom),
+        (KeyCode::F4, MapEdge::Left)
+    ] {
+        if keys.just_pressed(key) {
+            sim_command_queue.push(SimCommand::CycleBoundary(edge));
+        }
+    }
 }
 
 pub fn mouse_input(
-    time: Res<Time>,
+    time: Res<Time<Real>>,
+    keys: Res<Input<KeyCode>>,
     mut scroll_events: EventReader<MouseWheel>,
     mut camera_q: Query<(&OrthographicProjection, &mut MainCamera)>
 ) {
     let (ortho_proj, mut camera) = camera_q.get_single_mut().unwrap();
     
-    scroll_events.read().for_each(|event| {
+    // Shift+scroll adjusts filters instead, see edit_edges
+    let edge_editing = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    scroll_events.read().filter(|_| !edge_editing).for_each(|event| {
         camera.adjust_zoom_target(event.y.signum() != 1.0, time.elapsed_seconds_wrapped(), ortho_proj.scale);
     });
 }
 
+// Zooms towards the point under the cursor, or the middle of the screen when it's outside the window
 pub fn zoom_camera(
-    time: Res<Time>,
-    mut camera_q: Query<(&mut OrthographicProjection, &mut MainCamera)>
+    time: Res<Time<Real>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection, &mut MainCamera)>
+) {
+    let (mut camera_trans, mut ortho_proj, mut camera) = camera_q.get_single_mut().unwrap();
+    let scale = camera.interp_zoom(time.elapsed_seconds_wrapped());
+    if scale == ortho_proj.scale {
+        return;
+    }
+
+    if let Some(cursor) = window_q.get_single().ok().and_then(|window| {
+        window.cursor_position().map(|cursor| cursor - Vec2::new(window.width(), window.height()) / 2.0)
+    }) {
+        // Keep the world point under the cursor where it is
+        let offset = Vec2::new(cursor.x, -cursor.y) * (ortho_proj.scale - scale);
+        camera_trans.translation += offset.extend(0.0);
+    }
+    ortho_proj.scale = scale;
+}
+
+// C follows the selected MACC, or stops following, and J jumps to the hex with the most nanites
+pub fn camera_focus_input(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time<Real>>,
+    selected_macc: Res<SelectedMacc>,
+    (hex_grid, nanite_field, field_history): (Res<HexGrid>, Res<NaniteField>, Res<FieldHistory>),
+    mut camera_q: Query<(&Transform, &mut MainCamera)>
 ) {
-    let (mut ortho_proj, mut camera) = camera_q.get_single_mut().unwrap();
-    ortho_proj.scale = camera.interp_zoom(time.elapsed_seconds_wrapped());
+    let Ok((camera_trans, mut camera)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let start = camera_trans.translation.truncate();
+
+    if keys.just_pressed(KeyCode::C) {
+        match (camera.focus, selected_macc.get()) {
+            (Some(CameraFocus::Follow(_)), _) => camera.stop_focus(),
+            (_, Some(macc)) => camera.start_focus(CameraFocus::Follow(macc), time.elapsed_seconds_wrapped(), start),
+            _ => {}
+        }
+    }
+
+    if keys.just_pressed(KeyCode::J) {
+        // Looking back at the history jumps to the worst hex of that tick
+        let viewed = field_history.viewed();
+        let most_contaminated = hex_grid.hexes()
+            .filter_map(|pos| {
+                let nanite = match viewed {
+                    Some(entry) => entry.nanite(pos),
+                    None => nanite_field.nanite(pos)
+                };
+                nanite.map(|nanite| (pos, nanite.nanite_total))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((pos, _)) = most_contaminated {
+            let target = hex_grid.layout.pos_to_world((pos.0 as i32, pos.1 as i32));
+            camera.start_focus(CameraFocus::Point(target), time.elapsed_seconds_wrapped(), start);
+        }
+    }
+}
+
+// Double clicking centers the camera on the MACC or hex under the cursor
+pub fn double_click_focus(
+    mouse_input: Res<Input<MouseButton>>,
+    time: Res<Time<Real>>,
+    mouse_wrld_coords: Res<MouseWorldCoords>,
+    hex_grid: Res<HexGrid>,
+    macc_q: Query<&Transform, With<Macc>>,
+    mut camera_q: Query<(&Transform, &mut MainCamera)>,
+    mut last_click: Local<Option<(f32, Vec2)>>
+) {
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    let now = time.elapsed_seconds_wrapped();
+    let click = mouse_wrld_coords.0;
+    let double_click = last_click.is_some_and(|(last_time, last_pos)| {
+        now - last_time <= DOUBLE_CLICK_TIME && last_pos.distance(click) <= hex_grid.layout.radius / 2.0
+    });
+    if !double_click {
+        *last_click = Some((now, click));
+        return;
+    }
+    *last_click = None;
+
+    let Ok((camera_trans, mut camera)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let macc = macc_q.iter()
+        .map(|macc_trans| macc_trans.translation.truncate())
+        .filter(|macc_pos| macc_pos.distance(click) <= hex_grid.layout.radius)
+        .min_by(|a, b| a.distance(click).total_cmp(&b.distance(click)));
+    let focus = match (macc, hex_grid.get_at_world(click)) {
+        (Some(macc_pos), _) => CameraFocus::Point(macc_pos),
+        (None, Some(pos)) => CameraFocus::Point(hex_grid.layout.pos_to_world((pos.0 as i32, pos.1 as i32))),
+        (None, None) => return
+    };
+    camera.start_focus(focus, now, camera_trans.translation.truncate());
+}
+
+// Seconds between the two clicks of a double click
+const DOUBLE_CLICK_TIME: f32 = 0.35;
+
+// Moves the camera towards its focus
+pub fn update_camera_focus(
+    time: Res<Time<Real>>,
+    macc_q: Query<&Transform, (With<Macc>, Without<MainCamera>)>,
+    mut camera_q: Query<(&mut Transform, &mut MainCamera)>
+) {
+    let Ok((mut camera_trans, mut camera)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let now = time.elapsed_seconds_wrapped();
+    let target = match camera.focus {
+        None => return,
+        Some(CameraFocus::Point(target)) => {
+            if camera.pan_finished(now) {
+                camera.stop_focus();
+            }
+            target
+        }
+        Some(CameraFocus::Follow(macc_ent)) => match macc_q.get(macc_ent) {
+            Ok(macc_trans) => macc_trans.translation.truncate(),
+            Err(_) => {
+                camera.stop_focus();
+                return;
+            }
+        }
+    };
+    let position = camera.interp_pan(now, target);
+    camera_trans.translation.x = position.x;
+    camera_trans.translation.y = position.y;
+}
+
+// Keeps the middle of the screen over the map
+pub fn clamp_camera(
+    hex_grid: Res<HexGrid>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>
+) {
+    let Ok(mut camera_trans) = camera_q.get_single_mut() else {
+        return;
+    };
+    let bounds = hex_grid.world_bounds();
+    camera_trans.translation.x = camera_trans.translation.x.clamp(bounds.min.x, bounds.max.x);
+    camera_trans.translation.y = camera_trans.translation.y.clamp(bounds.min.y, bounds.max.y);
 }
 
 pub fn on_game_entity_click(
     mouse_wrld_coords: Res<MouseWorldCoords>,
     mouse_input: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
     rapier_context: Res<RapierContext>,
+    hex_grid: Res<HexGrid>,
     // mut event_writer: EventWriter<OnClickEvent>,
     mut game_event_writer: EventWriter<GameEvents>,
     click_signal_q: Query<&ClickSignal>
 ) {
+    // Shift+Left belongs to edit_edges, Shift+Right queues waypoints
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
     mouse_input.get_just_released().filter(|x| {
-        [MouseButton::Left, MouseButton::Right].contains(x)
+        **x == MouseButton::Right || (**x == MouseButton::Left && !shift)
     }).for_each(|input| {
+        // Hexes are picked through the layout, everything else through the colliders
+        if let Some(hex_pos) = hex_grid.get_at_world(mouse_wrld_coords.0) {
+            if input.eq(&MouseButton::Left) {
+                println!("Sending Hex Signal");
+                game_event_writer.send(GameEvents::HexSelect(hex_pos));
+            } else if input.eq(&MouseButton::Right) && shift {
+                game_event_writer.send(GameEvents::MaccQueueWaypoint(mouse_wrld_coords.0));
+            } else if input.eq(&MouseButton::Right) {
+                println!("Sening Macc Move order");
+                game_event_writer.send(GameEvents::MaccMoveOrder(mouse_wrld_coords.0));
+            }
+        }
+
         rapier_context.intersections_with_point(
             mouse_wrld_coords.0, 
             QueryFilter::default(), 
@@ -81,15 +436,6 @@ pub fn on_game_entity_click(
                 match click_signal_q.get(entity) {
                     Ok(signal) => {
                         match signal {
-                            ClickSignal::Hex => {
-                                if input.eq(&MouseButton::Left) {
-                                    println!("Sending Hex Signal");
-                                    game_event_writer.send(GameEvents::HexSelect(entity));
-                                } else if input.eq(&MouseButton::Right) {
-                                    println!("Sening Macc Move order");
-                                    game_event_writer.send(GameEvents::MaccMoveOrder(mouse_wrld_coords.0));
-                                }
-                            },
                             ClickSignal::Macc => {
                                 if input.eq(&MouseButton::Left) {
                                     println!("Sending macc select");
@@ -103,4 +449,23 @@ pub fn on_game_entity_click(
                 }
             })
     });
-}
\ No newline at end of file
+}
+// Shift+Left click cycles the edge under the cursor, Shift+scroll lets more or less through a filter
+pub fn edit_edges(
+    mouse_wrld_coords: Res<MouseWorldCoords>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut scroll_events: EventReader<MouseWheel>,
+    hex_grid: Res<HexGrid>,
+    mut sim_command_queue: ResMut<SimCommandQueue>
+) {
+    let steps: i32 = scroll_events.read().map(|event| event.y.signum() as i32).sum();
+    let Some((pos, direction)) = hex_grid.edge_at_world(mouse_wrld_coords.0) else {
+        return;
+    };
+    if mouse_input.just_released(MouseButton::Left) {
+        sim_command_queue.push(SimCommand::CycleEdge(pos, direction));
+    }
+    if steps != 0 && matches!(hex_grid.get_edge(pos, direction), EdgeAttribute::Filter(_)) {
+        sim_command_queue.push(SimCommand::StepEdgeFilter(pos, direction, steps));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod startup_systems;
 pub mod input_systems;
-pub mod continuous_systems;
\ No newline at end of file
+pub mod continuous_systems;
+pub mod sim_plugin;
@@ -0,0 +1,42 @@
+use bevy::{app::{App, Plugin}, ecs::{schedule::IntoSystemConfigs, system::Res}};
+
+use crate::resources::{sim_clock::{SimClock, SimTick}, replication::ReplicationSettings};
+
+use super::continuous_systems::{nanite_introduction, nanite_wind, nanite_dispersion, nanite_replication, hex_resource_regen, emitter_inject, scrubber_filter, build_funds_income, adjust_wind, barrier_track_wind, nanite_transient_apply};
+
+// The nanite simulation systems of one SimTick, shared by the game and nanite_batch
+pub struct SimTickPlugin;
+
+impl Plugin for SimTickPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // One fixed order so a seed always plays out the same:
+            // - the wind turns first, before anything reads it or draws from the SimRng
+            // - barriers follow the wind before it carries nanites against them
+            // - dispersion snapshots what the wind left behind
+            // - replication grows from the totals after dispersion
+            // - scrubbing takes its share of everything the tick added or removed
+            .add_systems(SimTick, (
+                    adjust_wind.run_if(every_ticks(10)),
+                    barrier_track_wind,
+                    nanite_introduction,
+                    nanite_wind,
+                    nanite_dispersion,
+                    nanite_replication.run_if(replication_enabled),
+                    hex_resource_regen,
+                    emitter_inject,
+                    scrubber_filter,
+                    build_funds_income,
+                    nanite_transient_apply
+                ).chain()
+            );
+    }
+}
+
+fn every_ticks(n: u64) -> impl Fn(Res<SimClock>) -> bool {
+    move |sim_clock: Res<SimClock>| sim_clock.tick.is_multiple_of(n)
+}
+
+fn replication_enabled(replication_settings: Res<ReplicationSettings>) -> bool {
+    replication_settings.enabled
+}
@@ -1,8 +1,15 @@
-use bevy::{ecs::{component::Component, system::{Commands, ResMut, Res}, entity::Entity, schedule::NextState}, core_pipeline::{core_2d::{Camera2dBundle, Camera2d}, clear_color::ClearColorConfig}, prelude::default, render::{color::Color, mesh::{Mesh, shape}, texture::Image}, math::{Vec2, Vec3}, sprite::{Mesh2dHandle, ColorMaterial, MaterialMesh2dBundle}, asset::{Assets, AssetServer, Handle}, transform::components::Transform, hierarchy::BuildChildren};
-use bevy_rapier2d::geometry::{Sensor, Collider};
+use bevy::{ecs::{component::Component, entity::Entity, system::{Commands, ResMut, Res}, schedule::NextState}, core_pipeline::{core_2d::{Camera2dBundle, Camera2d}, clear_color::ClearColorConfig}, prelude::default, render::{color::Color, mesh::Mesh, texture::Image}, math::Vec2, sprite::ColorMaterial, asset::{Assets, AssetServer, Handle}};
+use bevy_rapier2d::geometry::Collider;
 use bevy_rapier_collider_gen::single_convex_polyline_collider_translated;
 
-use crate::{resources::{weather::Weather, hex::{NaniteReserve, MapState, HexGrid}, input::{GameEntitiesClickable, MouseWorldCoords, SelectedMacc}, asset_handles::{AssetHandles, ColliderAssets, LoadingStates}}, bundles::{hex_bundle::HexBundle, macc_bundle::MaccBundle}, components::clickable::ClickSignal};
+use crate::{resources::{weather::Weather, hex::{NaniteReserve, MapState}, map::MapConfig, input::{GameEntitiesClickable, MouseWorldCoords, SelectedMacc}, replication::ReplicationSettings, build::BuildFunds, dispersion::DispersionSettings, sim_clock::SimClock, sim_rng::SimRng, replay::SimCommandQueue, history::FieldHistory, stats::{StatsRecorder, StatsHistory}, heatmap::HeatmapSettings, flow::{NaniteFlow, FlowOverlay}, asset_handles::{AssetHandles, ColliderAssets, LoadingStates}}, bundles::macc_bundle::MaccBundle, components::clickable::ClickSignal};
+
+// What the camera is moving to, a followed entity keeps it moving until something else takes over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraFocus {
+    Point(Vec2),
+    Follow(Entity)
+}
 
 #[derive(Component)]
 pub struct MainCamera {
@@ -12,7 +19,14 @@ pub struct MainCamera {
     scale_factor: f32,
     start_time: f32,
     zoom_duration: f32,
-    pub translation_speed: f32
+    // World units per second at a projection scale of 1
+    pub translation_speed: f32,
+    // Pixels from the window edge the cursor scrolls the map in, 0 turns it off
+    pub edge_scroll_margin: f32,
+    pan_start: Vec2,
+    pan_start_time: f32,
+    pan_duration: f32,
+    pub focus: Option<CameraFocus>
 }
 
 impl MainCamera {
@@ -26,6 +40,31 @@ impl MainCamera {
         }.clamp(self.scale_extents.0, self.scale_extents.1)
     }
 
+    pub fn start_focus(&mut self, focus: CameraFocus, start_time: f32, start: Vec2) {
+        self.focus = Some(focus);
+        self.pan_start = start;
+        self.pan_start_time = start_time;
+    }
+
+    // Manual panning takes the camera back
+    pub fn stop_focus(&mut self) {
+        self.focus = None;
+    }
+
+    // Same easing as interp_zoom, the target may move while panning
+    pub fn interp_pan(&self, current_time: f32, target: Vec2) -> Vec2 {
+        let t = (current_time - self.pan_start_time) / self.pan_duration;
+        if t >= 1.0 {
+            target
+        } else {
+            self.pan_start.lerp(target, t)
+        }
+    }
+
+    pub fn pan_finished(&self, current_time: f32) -> bool {
+        current_time - self.pan_start_time >= self.pan_duration
+    }
+
     pub fn interp_zoom(&mut self, current_time: f32) -> f32 {
         let t = (current_time - self.start_time) / self.zoom_duration;
         let lin_interp = (1.0 - t) * self.start_scale + t * self.target_scale;
@@ -39,6 +78,24 @@ impl MainCamera {
 
 pub fn setup(
     mut commands: Commands
+) {
+    commands.init_resource::<GameEntitiesClickable>();
+    commands.init_resource::<MapState>();
+    commands.init_resource::<SimClock>();
+    commands.init_resource::<SimCommandQueue>();
+    commands.init_resource::<HeatmapSettings>();
+    commands.init_resource::<FlowOverlay>();
+
+    commands.init_resource::<StatsHistory>();
+
+    if let Some(stats_recorder) = StatsRecorder::from_args() {
+        commands.insert_resource(stats_recorder);
+    }
+}
+
+// Simulation state a run starts from, also used to restart a replay
+pub fn setup_simulation(
+    mut commands: Commands
 ) {
     commands.insert_resource(Weather {
         wind_strength: 1.0,
@@ -49,9 +106,11 @@ pub fn setup(
         amount: 1000.0
     });
 
-    commands.init_resource::<GameEntitiesClickable>();
-    commands.init_resource::<MapState>();
-    commands.init_resource::<SelectedMacc>()
+    commands.insert_resource(SelectedMacc::default());
+    commands.insert_resource(ReplicationSettings::default());
+    commands.insert_resource(BuildFunds::default());
+    commands.insert_resource(DispersionSettings::default());
+    commands.insert_resource(NaniteFlow::default())
 }
 
 pub fn setup_camera(
@@ -71,7 +130,12 @@ pub fn setup_camera(
         scale_factor: 0.1,
         start_time: 0.0,
         zoom_duration: 0.25,
-        translation_speed: 10.0
+        translation_speed: 600.0,
+        edge_scroll_margin: 12.0,
+        pan_start: Vec2::ZERO,
+        pan_start_time: 0.0,
+        pan_duration: 0.4,
+        focus: None
     };
 
     commands.spawn((
@@ -85,12 +149,12 @@ pub fn setup_assets(
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    map_config: Res<MapConfig>
 ) {
     //Meshes
-    let outer_shape: Mesh = shape::RegularPolygon::new(50., 6).into(); 
+    let outer_shape: Mesh = map_config.layout.hex_mesh(map_config.layout.radius);
 
     let outer_shape_handle = meshes.add(outer_shape);
-    let inner_shape_handle: Mesh2dHandle = meshes.add(shape::RegularPolygon::new(49., 6).into()).into();
 
     let macc_handle: Handle<Image> = asset_server.load("macc.png");
 
@@ -98,7 +162,6 @@ pub fn setup_assets(
 
     commands.insert_resource(AssetHandles {
         out_hex_handle: outer_shape_handle.into(),
-        inner_hex_handle: inner_shape_handle,
         macc_image_handle: macc_handle,
         color_handle_white: color_handle_white,
     });
@@ -106,43 +169,24 @@ pub fn setup_assets(
 
 pub fn spawn_hexagons(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
     asset_handles: Res<AssetHandles>,
-    colliders: Res<ColliderAssets>
+    colliders: Res<ColliderAssets>,
+    map_config: Res<MapConfig>,
+    mut sim_rng: ResMut<SimRng>
 ) {
-    let grid_width: usize = 16;
-
-    let hex_grid: Vec<Vec<Entity>> = (0..grid_width).map(|row| {
-        (0..grid_width).map(|col| {
-            let hex_bundle = HexBundle::new(row, col, &asset_handles, &colliders);
-            
-            let ent = commands.spawn((hex_bundle, Sensor)).with_children(|parent| {
-                parent.spawn(MaterialMesh2dBundle {
-                    mesh: asset_handles.get_inner_hex_handle(),
-                    material: materials.add(ColorMaterial::from(Color::GRAY)),
-                    transform: Transform::from_translation(Vec3::new(0., 0., 1.0)),
-                    ..default()
-                });
-            }).id();
-
-            commands.entity(ent).insert(ClickSignal::Hex);
-
-            ent
-        }).collect()
-    }).collect();
-
-    commands.insert_resource(HexGrid {
-        grid: hex_grid,
-        selected_pos: None
-    });
+    // Hexes only get render entities per chunk, see update_visible_chunks
+    let (hex_grid, nanite_field) = map_config.generate(&mut *sim_rng);
+    commands.insert_resource(FieldHistory::new(nanite_field.dimensions));
+    commands.insert_resource(nanite_field);
+    commands.insert_resource(hex_grid);
 
-    let macc_1 = commands.spawn(MaccBundle::new(Vec2 {
+    let macc_1 = commands.spawn(MaccBundle::new(0, Vec2 {
         x: 0.0,
         y: 0.0,
     }, asset_handles.get_sprite_handle_macc(), colliders.get_macc())).id();
     commands.entity(macc_1).insert(ClickSignal::Macc);
 
-    let macc_2 = commands.spawn(MaccBundle::new(Vec2 {
+    let macc_2 = commands.spawn(MaccBundle::new(1, Vec2 {
         x: 5.0,
         y: 0.0,
     }, asset_handles.get_sprite_handle_macc(), colliders.get_macc())).id();
@@ -1,11 +1,20 @@
-use bevy::{ecs::{system::{Query, Res, ResMut}, event::EventReader, query::{With, Changed, Without}}, transform::components::Transform, math::{Quat, EulerRot, Vec2}, text::Text, render::view::Visibility, ui::{Interaction, widget::Button}, input::{mouse::MouseButton, Input}};
+use std::collections::HashSet;
+use bevy::{ecs::{system::{Query, Res, ResMut, Commands}, entity::Entity, event::{EventReader, EventWriter}, query::{With, Changed, Without}, change_detection::{DetectChanges, Ref}}, transform::components::{Transform, GlobalTransform}, math::{Quat, EulerRot, Vec2}, text::{Text, TextStyle, TextAlignment}, ui::{Interaction, widget::Button, Node, Style, Val, BackgroundColor, UiRect, PositionType, JustifyContent, AlignItems, Display, node_bundles::{NodeBundle, TextBundle, ButtonBundle}}, render::{view::{Visibility, ViewVisibility}, color::Color, camera::OrthographicProjection}, hierarchy::{BuildChildren, DespawnRecursiveExt}, prelude::default, window::{Window, PrimaryWindow}, input::{mouse::MouseButton, keyboard::KeyCode, Input}, asset::Assets, render::texture::Image, ui::UiImage};
 use bevy_rapier2d::{plugin::RapierContext, pipeline::QueryFilter, geometry::{Collider, CollisionGroups, Group}};
-use crate::{components::{grid_pos::GridPos, ui::{HexPosText, UICompass, RightInfoPane, ButtonOnClick, HexTerrainText, HexNaniteText}, terrain::Terrain, nanite::Nanite, macc::Macc, game_events::GameEvents}, resources::{weather::Weather, hex::{HexGrid, MapState}, input::GameEntitiesClickable}};
+use crate::{components::{grid_pos::GridPos, ui::{HexPosText, UICompass, RightInfoPane, ButtonOnClick, HexTerrainText, HexNaniteText, HexStructureText, BuildFundsText, SimClockText, TimelineTrack, TimelineHandle, TimelineLiveButton, TimelineText, HeatmapLegend, HeatmapLegendTitle, HeatmapLegendSwatch, HeatmapLegendLabel, Minimap, MinimapViewport, MinimapMacc, MaccInfoPane, MaccInfoText, HexMaccList, HexMaccEntry, HoverTooltip, HoverTooltipText, ChartsPanel, ChartsPanelButton, StatsChart, StatsChartText, HexSparkline, HexSparklineText}, clickable::ClickSignal, macc::{Macc, MaccId, MaccStatus, Team}, game_events::{GameEvents, SimCommand}, structure::{Emitter, Scrubber, Barrier}}, resources::{weather::Weather, hex::{HexGrid, MapState}, field::NaniteField, input::{GameEntitiesClickable, SelectedMacc, MouseWorldCoords}, build::BuildFunds, asset_handles::ColliderAssets, sim_clock::SimClock, replay::{Replay, SimCommandQueue}, history::FieldHistory, heatmap::HeatmapSettings, flow::{FlowOverlay, NaniteFlow}, minimap::MinimapLayout, stats::StatsHistory}, systems::game::startup_systems::MainCamera};
+
+use super::theme::{BOARDER_COLOR, TEXT_COLOR};
 
 pub fn update_compass(
     weather: Res<Weather>,
+    field_history: Option<Res<FieldHistory>>,
     mut compass_q: Query<(&UICompass, &mut Transform)>
 ) {
+    // Looking back at the history shows the wind of that tick
+    let weather = match field_history.as_ref().and_then(|history| history.viewed()) {
+        Some(entry) => &entry.weather,
+        None => weather.as_ref()
+    };
     match compass_q.get_single_mut() {
         Ok((_, mut trans)) => {
             trans.rotation = Quat::from_euler(EulerRot::XYZ, 
@@ -20,46 +29,68 @@ pub fn update_compass(
 
 pub fn update_nanite_info_pane(
     hex_grid: Res<HexGrid>,
-    nanite_q: Query<&Nanite, Changed<Nanite>>,
+    nanite_field: Res<NaniteField>,
     mut nanite_text_q: Query<&mut Text, With<HexNaniteText>>
 ) {
-    if let Some(selected_ent) = hex_grid.get_selected() {
-        match (nanite_q.get(selected_ent), nanite_text_q.get_single_mut()) {
-            (Ok(nanite), Ok(mut nanite_text)) => {
-                nanite_text.sections.first_mut().unwrap().value = format!("Nanites\n{} / {}", nanite.nanite_total, nanite.nanite_capacity);
-            },
-            _ => {}
+    if let Some(selected_pos) = hex_grid.get_selected() {
+        if let (Some(nanite), Ok(mut nanite_text)) = (nanite_field.nanite(selected_pos), nanite_text_q.get_single_mut()) {
+            nanite_text.sections.first_mut().unwrap().value = format!("Nanites\n{} / {}", nanite.nanite_total, nanite.nanite_capacity);
+        }
+    }
+}
+
+type HexStructureQuery<'a> = (&'a GridPos, Option<&'a Emitter>, Option<&'a Scrubber>, Option<&'a Barrier>);
+
+pub fn update_structure_info_pane(
+    hex_grid: Res<HexGrid>,
+    build_funds: Res<BuildFunds>,
+    structure_q: Query<HexStructureQuery>,
+    mut structure_text_q: Query<&mut Text, (With<HexStructureText>, Without<BuildFundsText>)>,
+    mut funds_text_q: Query<&mut Text, (With<BuildFundsText>, Without<HexStructureText>)>
+) {
+    if let Ok(mut funds_text) = funds_text_q.get_single_mut() {
+        funds_text.sections.first_mut().unwrap().value = format!("Funds: {:.0}", build_funds.amount);
+    }
+
+    if let Some(selected_pos) = hex_grid.get_selected() {
+        if let Ok(mut structure_text) = structure_text_q.get_single_mut() {
+            let mut structures: Vec<String> = Vec::new();
+            for (_, emitter, scrubber, barrier) in structure_q.iter().filter(|(grid_pos, ..)| grid_pos.pos == selected_pos) {
+                if emitter.is_some() {
+                    structures.push("Emitter".to_string());
+                }
+                if let Some(scrubber) = scrubber {
+                    structures.push(format!("Scrubber (radius {})", scrubber.radius));
+                }
+                if let Some(barrier) = barrier {
+                    structures.push(format!("Barrier x{}", barrier.edges.len()));
+                }
+            }
+            if structures.is_empty() {
+                structures.push("None".to_string());
+            }
+            structure_text.sections.first_mut().unwrap().value = format!("Structures\n{}", structures.join("\n"));
         }
     }
 }
 
 pub fn ui_game_event_react(
     mut hex_grid: ResMut<HexGrid>,
-    rapier_context: Res<RapierContext>,
+    nanite_field: Res<NaniteField>,
     mut game_event_reader: EventReader<GameEvents>,
-    hex_q: Query<(&GridPos, &Terrain, &Transform, &Collider)>,
     mut pos_text_q: Query<&mut Text, (With<HexPosText>, Without<HexTerrainText>)>,
     mut terrain_text_q: Query<&mut Text, (With<HexTerrainText>, Without<HexPosText>)>,
-    mut info_pane_q: Query<&mut Visibility, With<RightInfoPane>>,
-    test_q: Query<(Option<&Nanite>, Option<&Macc>)>
+    mut info_pane_q: Query<&mut Visibility, With<RightInfoPane>>
 ) {
     for event in game_event_reader.read() {
         match event {
-            GameEvents::HexSelect(ent) => {
-                match (hex_q.get(*ent), pos_text_q.get_single_mut(), terrain_text_q.get_single_mut(), info_pane_q.get_single_mut()) {
-                    (Ok((grid_pos, terrain, hex_trans, hex_coll)), Ok(mut pos_text), Ok(mut terrain_text), Ok(mut info_pane_vis)) => {
-                        *info_pane_vis = Visibility::Visible;
-                        pos_text.sections.first_mut().unwrap().value = format!("Coordinates\n{}", grid_pos.to_string());
-                        terrain_text.sections.first_mut().unwrap().value = format!("Terrain Type\n{}", terrain.to_string());
-                        hex_grid.select_pos(grid_pos.pos.clone());
-                        get_maccs_in_hex(
-                            &rapier_context, 
-                            hex_coll, 
-                            hex_trans.translation.truncate(),
-                            &test_q
-                        );
-                    },
-                    _ => {}
+            GameEvents::HexSelect(pos) => {
+                if let (Some(terrain), Ok(mut pos_text), Ok(mut terrain_text), Ok(mut info_pane_vis)) = (nanite_field.terrain(*pos), pos_text_q.get_single_mut(), terrain_text_q.get_single_mut(), info_pane_q.get_single_mut()) {
+                    let grid_pos = GridPos { pos: *pos };
+                    *info_pane_vis = Visibility::Visible;
+                    pos_text.sections.first_mut().unwrap().value = format!("Coordinates\n{}", grid_pos);
+                    terrain_text.sections.first_mut().unwrap().value = format!("Terrain Type\n{}", terrain);
+                    hex_grid.select_pos(*pos);
                 }
             },
             GameEvents::MaccSelect(_) => {
@@ -68,6 +99,8 @@ pub fn ui_game_event_react(
             GameEvents::MaccMoveOrder(_) => {
 
             },
+            GameEvents::MaccQueueWaypoint(_) | GameEvents::MaccOrder(..) => {},
+            GameEvents::BuildStructure(_, _) => {}
         }
     }
 }
@@ -75,25 +108,204 @@ pub fn ui_game_event_react(
 fn get_maccs_in_hex(
     rapier_context: &RapierContext,
     hex_collider: &Collider,
-    hex_pos: Vec2,
-    test_q: &Query<(Option<&Nanite>, Option<&Macc>)>
-) {
+    hex_pos: Vec2
+) -> Vec<Entity> {
+    let mut maccs = Vec::new();
     rapier_context.intersections_with_shape(
         hex_pos, 
         0.0, 
         hex_collider, 
         QueryFilter::default().groups(CollisionGroups::new(Group::ALL, Group::GROUP_2)), 
         |ent| {
-            println!("Get Maccs result\n{:?}", test_q.get(ent));
+            maccs.push(ent);
             true
         }
     );
+    maccs
+}
+
+type HexMaccQuery<'a> = (&'a MaccId, &'a Team, &'a MaccStatus);
+
+// Lists the MACCs in the selected hex with what they are exposed to, the entries are
+// respawned when units come or go and their text kept current
+pub fn update_hex_macc_list(
+    mut commands: Commands,
+    (hex_grid, nanite_field, rapier_context, colliders): (Res<HexGrid>, Res<NaniteField>, Res<RapierContext>, Res<ColliderAssets>),
+    macc_q: Query<HexMaccQuery, With<Macc>>,
+    mut list_q: Query<(Entity, &mut HexMaccList)>,
+    mut entry_q: Query<(&HexMaccEntry, &mut Text)>
+) {
+    let (Some(selected_pos), Ok((list_ent, mut macc_list))) = (hex_grid.get_selected(), list_q.get_single_mut()) else {
+        return;
+    };
+    let hex_center = hex_grid.layout.pos_to_world((selected_pos.0 as i32, selected_pos.1 as i32));
+    let mut maccs: Vec<Entity> = get_maccs_in_hex(&rapier_context, &colliders.get_hex(), hex_center).into_iter()
+        .filter(|ent| macc_q.contains(*ent))
+        .collect();
+    maccs.sort_by_key(|ent| macc_q.get(*ent).map(|(macc_id, ..)| macc_id.0).unwrap_or(u32::MAX));
+    let exposure = nanite_field.nanite(selected_pos).map_or(0.0, |nanite| nanite.nanite_total);
+    let entry_text = |ent: Entity| match macc_q.get(ent) {
+        Ok((macc_id, team, status)) => format!("MACC {} (Team {:?})\nExposure {:.1}, Health {:.0}", macc_id.0, team, exposure, status.health),
+        Err(_) => String::new()
+    };
+
+    if macc_list.0.as_ref() != Some(&maccs) {
+        commands.entity(list_ent).despawn_descendants();
+        commands.entity(list_ent).with_children(|list| {
+            if maccs.is_empty() {
+                list.spawn(TextBundle::from_section("No MACCs", TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }));
+            }
+            for macc_ent in maccs.iter() {
+                list.spawn((ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                        margin: UiRect::all(Val::Px(2.)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::WHITE.into(),
+                    border_color: BOARDER_COLOR.into(),
+                    ..default()
+                }, ButtonOnClick::SelectMacc(*macc_ent)))
+                .with_children(|entry_button| {
+                    entry_button.spawn((TextBundle::from_section(
+                        entry_text(*macc_ent),
+                        TextStyle {
+                            font_size: 16.0,
+                            color: TEXT_COLOR,
+                            ..default()
+                        }
+                    ).with_text_alignment(TextAlignment::Center), HexMaccEntry(*macc_ent)));
+                });
+            }
+        });
+        macc_list.0 = Some(maccs);
+        return;
+    }
+
+    for (entry, mut text) in entry_q.iter_mut() {
+        let value = entry_text(entry.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+type HoverMaccQuery<'a> = (&'a MaccId, &'a Team, &'a MaccStatus, &'a Macc);
+type HoverTooltipQuery<'a> = (&'a mut Style, &'a mut Visibility);
+type UiNodeQuery<'a> = (&'a Node, &'a GlobalTransform, &'a ViewVisibility, &'a BackgroundColor);
+type UiNodeFilter = Without<HoverTooltip>;
+
+// Details of the MACC or hex under the cursor, hidden while the cursor is over the UI.
+// Only reads what's there so clicks go through to on_game_entity_click as before
+pub fn update_hover_tooltip(
+    (mouse_wrld_coords, hex_grid, keys): (Res<MouseWorldCoords>, Res<HexGrid>, Res<Input<KeyCode>>),
+    (nanite_field, weather, nanite_flow): (Res<NaniteField>, Res<Weather>, Res<NaniteFlow>),
+    (rapier_context, field_history): (Res<RapierContext>, Option<Res<FieldHistory>>),
+    (window_q, ui_node_q): (Query<&Window, With<PrimaryWindow>>, Query<UiNodeQuery, UiNodeFilter>),
+    (click_signal_q, macc_q, structure_q): (Query<&ClickSignal>, Query<HoverMaccQuery>, Query<HexStructureQuery>),
+    mut tooltip_q: Query<HoverTooltipQuery, With<HoverTooltip>>,
+    mut tooltip_text_q: Query<&mut Text, With<HoverTooltipText>>
+) {
+    let (Ok((mut style, mut vis)), Ok(mut text)) = (tooltip_q.get_single_mut(), tooltip_text_q.get_single_mut()) else {
+        return;
+    };
+    let cursor = window_q.get_single().ok().and_then(|window| window.cursor_position());
+    // Any panel drawn under the cursor hides the map, layout nodes without a background don't
+    let over_ui = |cursor: &Vec2| ui_node_q.iter().any(|(node, transform, view_visibility, background)| {
+        view_visibility.get() && !background.0.is_fully_transparent() && node.logical_rect(transform).contains(*cursor)
+    });
+    let Some(cursor) = cursor.filter(|cursor| !over_ui(cursor)) else {
+        style.display = Display::None;
+        return;
+    };
+
+    let mut hovered_macc = None;
+    rapier_context.intersections_with_point(mouse_wrld_coords.0, QueryFilter::default(), |entity| {
+        match click_signal_q.get(entity) {
+            Ok(ClickSignal::Macc) => {
+                hovered_macc = Some(entity);
+                false
+            },
+            Err(_) => true
+        }
+    });
+
+    let value = if let Some((macc_id, team, status, macc)) = hovered_macc.and_then(|ent| macc_q.get(ent).ok()) {
+        let movement = if status.disabled() {
+            "Disabled".to_string()
+        } else if macc.hold {
+            "Holding".to_string()
+        } else if macc.in_position(macc.position) && macc.waypoints.is_empty() {
+            "Idle".to_string()
+        } else {
+            format!("Moving, {} waypoint(s)", macc.waypoints.len())
+        };
+        format!("MACC {} (Team {:?})\n{}\nHealth {:.0} / {:.0}\nCargo {:.1} / {:.0}",
+            macc_id.0, team, movement, status.health, MaccStatus::MAX_HEALTH, status.cargo, MaccStatus::CARGO_CAPACITY)
+    } else if let Some(pos) = hex_grid.get_at_world(mouse_wrld_coords.0) {
+        // Looking back at the history shows that tick's nanites and wind
+        let viewed = field_history.as_ref().and_then(|history| history.viewed());
+        let (nanite, weather, wind_flow) = match viewed {
+            Some(entry) => (entry.nanite(pos), &entry.weather, None),
+            None => (nanite_field.nanite(pos), weather.as_ref(), nanite_flow.wind.get(hex_grid.index(pos)).copied())
+        };
+        let mut lines = vec![
+            format!("Hex {}", GridPos { pos }.to_string()),
+            format!("Terrain {}", nanite_field.terrain(pos).map_or("None".to_string(), |terrain| terrain.to_string()))
+        ];
+        if let Some(nanite) = nanite {
+            lines.push(format!("Nanites {:.1} / {:.1}", nanite.nanite_total, nanite.nanite_capacity));
+        }
+        lines.push(match wind_flow {
+            Some(wind_flow) => format!("Wind {:.1} at {:.0}°, {:+.2} carried in", weather.wind_strength, weather.wind_direction, wind_flow),
+            None => format!("Wind {:.1} at {:.0}°", weather.wind_strength, weather.wind_direction)
+        });
+        for (_, emitter, scrubber, barrier) in structure_q.iter().filter(|(grid_pos, ..)| grid_pos.pos == pos) {
+            if emitter.is_some() {
+                lines.push("Emitter".to_string());
+            }
+            if let Some(scrubber) = scrubber {
+                lines.push(format!("Scrubber (radius {})", scrubber.radius));
+            }
+            if let Some(barrier) = barrier {
+                lines.push(format!("Barrier x{}", barrier.edges.len()));
+            }
+        }
+        // Editing edges shows what the one under the cursor lets through
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            if let Some((edge_pos, direction)) = hex_grid.edge_at_world(mouse_wrld_coords.0) {
+                lines.push(format!("Edge {:?}: {}", direction, hex_grid.get_edge(edge_pos, direction)));
+            }
+        }
+        lines.join("\n")
+    } else {
+        style.display = Display::None;
+        return;
+    };
+
+    // Offset from the cursor so it doesn't cover what's being pointed at
+    style.display = Display::Flex;
+    style.left = Val::Px(cursor.x + 16.0);
+    style.top = Val::Px(cursor.y + 16.0);
+    *vis = Visibility::Inherited;
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
 }
 
 pub fn ui_button_system(
     mut map_state: ResMut<MapState>,
     mut hex_grid: ResMut<HexGrid>,
     mut game_entities_clickable: ResMut<GameEntitiesClickable>,
+    mut game_event_writer: EventWriter<GameEvents>,
+    (mut sim_clock, mut flow_overlay, selected_macc, mut sim_command_queue): (ResMut<SimClock>, ResMut<FlowOverlay>, Res<SelectedMacc>, ResMut<SimCommandQueue>),
     interaction_query: Query<
         (
             &Interaction,
@@ -112,8 +324,23 @@ pub fn ui_button_system(
                         *info_pane_vis_q.get_single_mut().unwrap() = Visibility::Hidden;
                         hex_grid.deselect_pos();
                     }
-                    ButtonOnClick::MapButtonTerrain => *map_state = MapState::Terrain,
-                    ButtonOnClick::MapButtonNanite => *map_state = MapState::Nanite,
+                    ButtonOnClick::MapButton(state) => *map_state = *state,
+                    ButtonOnClick::FlowOverlay => flow_overlay.enabled = !flow_overlay.enabled,
+                    ButtonOnClick::MaccPaneClose => sim_command_queue.push(SimCommand::MaccDeselect),
+                    ButtonOnClick::SelectMacc(macc) => game_event_writer.send(GameEvents::MaccSelect(*macc)),
+                    ButtonOnClick::MaccOrder(order) => {
+                        if let Some(macc) = selected_macc.get() {
+                            game_event_writer.send(GameEvents::MaccOrder(macc, *order));
+                        }
+                    }
+                    ButtonOnClick::Build(kind) => {
+                        if let Some(hex_pos) = hex_grid.get_selected() {
+                            game_event_writer.send(GameEvents::BuildStructure(hex_pos, *kind));
+                        }
+                    }
+                    ButtonOnClick::SimPause => sim_clock.toggle_pause(),
+                    ButtonOnClick::SimStep => sim_clock.step(),
+                    ButtonOnClick::SimSpeed(speed) => sim_clock.speed = *speed,
                 }
             },
             Interaction::Hovered => {},
@@ -122,6 +349,251 @@ pub fn ui_button_system(
     }
 }
 
+pub fn update_sim_clock_text(
+    sim_clock: Res<SimClock>,
+    replay: Res<Replay>,
+    mut sim_clock_text_q: Query<&mut Text, With<SimClockText>>
+) {
+    if !sim_clock.is_changed() {
+        return;
+    }
+    if let Ok(mut sim_clock_text) = sim_clock_text_q.get_single_mut() {
+        let tick = if replay.is_playback() {
+            format!("Replay {}/{}", sim_clock.tick, replay.length)
+        } else {
+            format!("Tick {}", sim_clock.tick)
+        };
+        sim_clock_text.sections[0].value = if sim_clock.paused {
+            format!("{} - Paused", tick)
+        } else {
+            format!("{} - {}", tick, sim_clock.speed)
+        };
+    }
+}
+
+// Dragging along the timeline track looks back at that tick, the right end and
+// the Live button go back to the live field
+pub fn timeline_input(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    track_q: Query<(&Interaction, &Node, &GlobalTransform), With<TimelineTrack>>,
+    live_q: Query<&Interaction, (Changed<Interaction>, With<TimelineLiveButton>)>,
+    mut field_history: ResMut<FieldHistory>,
+    mut map_state: ResMut<MapState>,
+    mut game_entities_clickable: ResMut<GameEntitiesClickable>
+) {
+    if live_q.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        game_entities_clickable.0 = false;
+        field_history.view_live();
+        return;
+    }
+
+    let (Ok((interaction, node, transform)), Ok(window)) = (track_q.get_single(), window_q.get_single()) else {
+        return;
+    };
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    game_entities_clickable.0 = false;
+    let (Some(cursor), Some((first, last))) = (window.cursor_position(), field_history.range()) else {
+        return;
+    };
+
+    let left = transform.translation().x - node.size().x / 2.0;
+    let fraction = ((cursor.x - left) / node.size().x.max(1.0)).clamp(0.0, 1.0);
+    let tick = first + (fraction * (last - first) as f32).round() as u64;
+    if tick >= last {
+        field_history.view_live();
+    } else if field_history.viewing() != Some(tick) {
+        field_history.view(tick);
+        // Terrain is the only overlay that doesn't change over time
+        if *map_state == MapState::Terrain {
+            *map_state = MapState::Nanite;
+        }
+    }
+}
+
+pub fn update_timeline(
+    field_history: Res<FieldHistory>,
+    mut handle_q: Query<&mut Style, With<TimelineHandle>>,
+    mut timeline_text_q: Query<&mut Text, With<TimelineText>>
+) {
+    if !field_history.is_changed() {
+        return;
+    }
+    let fraction = match (field_history.viewing(), field_history.range()) {
+        (Some(tick), Some((first, last))) if last > first => (tick - first) as f32 / (last - first) as f32,
+        _ => 1.0
+    };
+    if let Ok(mut handle_style) = handle_q.get_single_mut() {
+        handle_style.left = Val::Percent(fraction * 100.0);
+    }
+    if let Ok(mut timeline_text) = timeline_text_q.get_single_mut() {
+        timeline_text.sections[0].value = match field_history.viewing() {
+            Some(tick) => format!("Tick {}", tick),
+            None => "Live".to_string()
+        };
+    }
+}
+
+pub fn update_heatmap_legend(
+    heatmap_settings: Res<HeatmapSettings>,
+    map_state: Res<MapState>,
+    mut legend_q: Query<&mut Visibility, With<HeatmapLegend>>,
+    mut title_q: Query<&mut Text, (With<HeatmapLegendTitle>, Without<HeatmapLegendLabel>)>,
+    mut swatch_q: Query<(&HeatmapLegendSwatch, &mut BackgroundColor)>,
+    mut label_q: Query<(&HeatmapLegendLabel, &mut Text), Without<HeatmapLegendTitle>>
+) {
+    if map_state.is_changed() {
+        if let Ok(mut visibility) = legend_q.get_single_mut() {
+            *visibility = if map_state.uses_heatmap() {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+    if !heatmap_settings.is_changed() {
+        return;
+    }
+    if let Ok(mut title) = title_q.get_single_mut() {
+        title.sections[0].value = format!("Nanites ({}, {})", heatmap_settings.ramp, heatmap_settings.scale);
+    }
+    for (swatch, mut background) in swatch_q.iter_mut() {
+        *background = heatmap_settings.ramp.sample(swatch.0).into();
+    }
+    for (label, mut text) in label_q.iter_mut() {
+        text.sections[0].value = format!("{:.1}", heatmap_settings.value_at(label.0));
+    }
+}
+
+// Outline of what the camera sees and a dot per MACC over the minimap
+pub fn update_minimap_overlay(
+    mut commands: Commands,
+    minimap_layout: Res<MinimapLayout>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    macc_q: Query<(Entity, &Transform, &Team), With<Macc>>,
+    minimap_q: Query<Entity, With<Minimap>>,
+    mut viewport_q: Query<&mut Style, With<MinimapViewport>>,
+    mut marker_q: Query<(Entity, &MinimapMacc, &mut Style), Without<MinimapViewport>>
+) {
+    let (Ok((camera_trans, projection)), Ok(minimap)) = (camera_q.get_single(), minimap_q.get_single()) else {
+        return;
+    };
+    if let Ok(mut viewport_style) = viewport_q.get_single_mut() {
+        let top_left = minimap_layout.world_to_fraction(camera_trans.translation.truncate() + Vec2::new(projection.area.min.x, projection.area.max.y));
+        let bottom_right = minimap_layout.world_to_fraction(camera_trans.translation.truncate() + Vec2::new(projection.area.max.x, projection.area.min.y));
+        viewport_style.left = Val::Percent(top_left.x * 100.0);
+        viewport_style.top = Val::Percent(top_left.y * 100.0);
+        viewport_style.width = Val::Percent((bottom_right.x - top_left.x) * 100.0);
+        viewport_style.height = Val::Percent((bottom_right.y - top_left.y) * 100.0);
+    }
+
+    let mut marked: HashSet<Entity> = HashSet::new();
+    for (marker_ent, marker, mut marker_style) in marker_q.iter_mut() {
+        let Ok((_, macc_trans, _)) = macc_q.get(marker.0) else {
+            commands.entity(marker_ent).despawn_recursive();
+            continue;
+        };
+        marked.insert(marker.0);
+        let fraction = minimap_layout.world_to_fraction(macc_trans.translation.truncate());
+        marker_style.left = Val::Percent(fraction.x * 100.0);
+        marker_style.top = Val::Percent(fraction.y * 100.0);
+    }
+
+    for (macc_ent, macc_trans, team) in macc_q.iter().filter(|(macc_ent, _, _)| !marked.contains(macc_ent)) {
+        let fraction = minimap_layout.world_to_fraction(macc_trans.translation.truncate());
+        let marker = commands.spawn((NodeBundle {
+            style: Style {
+                width: Val::Px(4.),
+                height: Val::Px(4.),
+                left: Val::Percent(fraction.x * 100.0),
+                top: Val::Percent(fraction.y * 100.0),
+                margin: UiRect::all(Val::Px(-2.)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: Color::from(team).into(),
+            ..default()
+        }, MinimapMacc(macc_ent))).id();
+        commands.entity(minimap).add_child(marker);
+    }
+}
+
+// Clicking or dragging on the minimap centers the camera there
+pub fn minimap_input(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    minimap_q: Query<(&Interaction, &Node, &GlobalTransform), With<Minimap>>,
+    minimap_layout: Res<MinimapLayout>,
+    mut camera_q: Query<(&mut Transform, &mut MainCamera)>,
+    mut game_entities_clickable: ResMut<GameEntitiesClickable>
+) {
+    let (Ok((interaction, node, transform)), Ok(window)) = (minimap_q.get_single(), window_q.get_single()) else {
+        return;
+    };
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    game_entities_clickable.0 = false;
+    let (Some(cursor), Ok((mut camera_trans, mut camera))) = (window.cursor_position(), camera_q.get_single_mut()) else {
+        return;
+    };
+    camera.stop_focus();
+
+    let top_left = transform.translation().truncate() - node.size() / 2.0;
+    let fraction = ((cursor - top_left) / node.size().max(Vec2::ONE)).clamp(Vec2::ZERO, Vec2::ONE);
+    let target = minimap_layout.fraction_to_world(fraction);
+    camera_trans.translation.x = target.x;
+    camera_trans.translation.y = target.y;
+}
+
+type MaccInfoQuery<'a> = (&'a MaccId, &'a Team, &'a Transform, &'a Macc, &'a MaccStatus);
+
+// Shows the unit panel while a MACC is selected and keeps it current
+pub fn update_macc_info_pane(
+    selected_macc: Res<SelectedMacc>,
+    hex_grid: Res<HexGrid>,
+    macc_q: Query<MaccInfoQuery>,
+    mut pane_q: Query<&mut Visibility, With<MaccInfoPane>>,
+    mut text_q: Query<(&mut Text, &MaccInfoText)>
+) {
+    let Ok(mut pane_visibility) = pane_q.get_single_mut() else {
+        return;
+    };
+    let Some((macc_id, team, trans, macc, status)) = selected_macc.get().and_then(|macc| macc_q.get(macc).ok()) else {
+        *pane_visibility = Visibility::Hidden;
+        return;
+    };
+    *pane_visibility = Visibility::Visible;
+
+    let position = trans.translation.truncate();
+    // Degrees clockwise from up, like the wind
+    let up = trans.up().truncate();
+    let heading = up.x.atan2(up.y).to_degrees().rem_euclid(360.0);
+    let hex = match hex_grid.get_at_world(position) {
+        Some(pos) => GridPos { pos }.to_string(),
+        None => "Off map".to_string()
+    };
+    let orders = if macc.hold {
+        "Holding".to_string()
+    } else if macc.in_position(macc.position) && macc.waypoints.is_empty() {
+        "Idle".to_string()
+    } else {
+        format!("Target ({:.0}, {:.0}), {} waypoints", macc.target_position.x, macc.target_position.y, macc.waypoints.len())
+    };
+    let condition = if status.disabled() { " (disabled)" } else { "" };
+
+    for (mut text, info_text) in text_q.iter_mut() {
+        text.sections[0].value = match info_text {
+            MaccInfoText::Identity => format!("MACC {}\nTeam {:?}\nHex {}", macc_id.0, team, hex),
+            MaccInfoText::Movement => format!("{}\nSpeed {:.1}\nHeading {:.0}", orders, macc.speed, heading),
+            MaccInfoText::Condition => format!(
+                "Health {:.0} / {:.0}{}\nCargo {:.1} / {:.0}",
+                status.health, MaccStatus::MAX_HEALTH, condition, status.cargo, MaccStatus::CARGO_CAPACITY
+            ),
+        };
+    }
+}
+
 pub fn reset_game_entities_clickable(
     mut game_entities_clickable: ResMut<GameEntitiesClickable>,
     mut mouse_input: ResMut<Input<MouseButton>>
@@ -129,4 +601,99 @@ pub fn reset_game_entities_clickable(
     if !game_entities_clickable.0 && mouse_input.clear_just_released(MouseButton::Left) {
         game_entities_clickable.0 = true;
     }
-}
\ No newline at end of file
+}
+
+// Draws values left to right across the whole image, scaled to their range with 0 kept in view
+fn plot_series(image: &mut Image, values: &[f32], color: Color) {
+    let (width, height) = (image.texture_descriptor.size.width as usize, image.texture_descriptor.size.height as usize);
+    image.data.fill(0);
+    if values.is_empty() || height == 0 {
+        return;
+    }
+    let min = values.iter().copied().fold(0.0, f32::min);
+    let max = values.iter().copied().fold(min, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+    let color = color.as_rgba_u8();
+    let mut previous: Option<usize> = None;
+    for x in 0..width {
+        let value = values[x * values.len() / width];
+        let row = height - 1 - (((value - min) / range) * (height - 1) as f32).round() as usize;
+        // Joined to the last column so steep changes stay one line
+        let (top, bottom) = previous.map_or((row, row), |previous| (row.min(previous), row.max(previous)));
+        for y in top..=bottom {
+            let index = (y * width + x) * 4;
+            image.data[index..index + 4].copy_from_slice(&color);
+        }
+        previous = Some(row);
+    }
+}
+
+// K or the map bar button show and hide the charts panel
+pub fn charts_panel_input(
+    keys: Res<Input<KeyCode>>,
+    mut game_entities_clickable: ResMut<GameEntitiesClickable>,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<ChartsPanelButton>)>,
+    mut panel_q: Query<&mut Visibility, With<ChartsPanel>>
+) {
+    let clicked = button_q.iter().any(|interaction| *interaction == Interaction::Pressed);
+    if clicked {
+        game_entities_clickable.0 = false;
+    }
+    if !clicked && !keys.just_pressed(KeyCode::K) {
+        return;
+    }
+    if let Ok(mut vis) = panel_q.get_single_mut() {
+        *vis = match *vis {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+// Replots the charts from the StatsHistory after every recorded tick while they're shown
+pub fn update_stats_charts(
+    mut images: ResMut<Assets<Image>>,
+    stats_history: Res<StatsHistory>,
+    panel_q: Query<Ref<Visibility>, With<ChartsPanel>>,
+    chart_q: Query<(&StatsChart, &UiImage)>,
+    mut chart_text_q: Query<(&StatsChartText, &mut Text)>
+) {
+    let Ok(panel_vis) = panel_q.get_single() else {
+        return;
+    };
+    if *panel_vis == Visibility::Hidden || !(stats_history.is_changed() || panel_vis.is_changed()) {
+        return;
+    }
+
+    for (chart, ui_image) in chart_q.iter() {
+        if let Some(image) = images.get_mut(&ui_image.texture) {
+            plot_series(image, &stats_history.series(chart.0), chart.0.color());
+        }
+    }
+    for (chart_text, mut text) in chart_text_q.iter_mut() {
+        let value = stats_history.latest().map_or(0.0, |stats| chart_text.0.value(stats));
+        text.sections[0].value = format!("{} {:.1}", chart_text.0, value);
+    }
+}
+
+// Nanites on the selected hex over the ticks the StatsHistory keeps
+pub fn update_hex_sparkline(
+    mut images: ResMut<Assets<Image>>,
+    hex_grid: Res<HexGrid>,
+    stats_history: Res<StatsHistory>,
+    sparkline_q: Query<&UiImage, With<HexSparkline>>,
+    mut sparkline_text_q: Query<&mut Text, With<HexSparklineText>>
+) {
+    if !(hex_grid.is_changed() || stats_history.is_changed()) {
+        return;
+    }
+    let (Some(selected_pos), Ok(ui_image), Ok(mut text)) = (hex_grid.get_selected(), sparkline_q.get_single(), sparkline_text_q.get_single_mut()) else {
+        return;
+    };
+    let values = stats_history.hex_series(hex_grid.index(selected_pos));
+    if let Some(image) = images.get_mut(&ui_image.texture) {
+        plot_series(image, &values, TEXT_COLOR);
+    }
+    let max = values.iter().copied().fold(0.0, f32::max);
+    text.sections[0].value = format!("Last {} ticks, peak {:.1}", values.len(), max);
+}
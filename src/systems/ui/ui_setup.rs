@@ -1,11 +1,22 @@
-use bevy::{ecs::system::Commands, ui::{node_bundles::{NodeBundle, TextBundle, ButtonBundle}, Style, Val, JustifyContent, UiRect, AlignItems, FlexDirection, AlignContent, PositionType, FlexWrap, Display}, prelude::default, hierarchy::BuildChildren, render::{color::Color, view::Visibility}, text::{TextStyle, TextAlignment, Text}};
+use bevy::{ecs::system::{Commands, ResMut}, asset::{Assets, Handle}, render::{texture::Image, render_resource::{Extent3d, TextureDimension, TextureFormat}}, ui::{UiImage, node_bundles::{NodeBundle, TextBundle, ButtonBundle, ImageBundle}, Style, Val, JustifyContent, UiRect, AlignItems, FlexDirection, AlignContent, PositionType, FlexWrap, Display, Overflow, ZIndex}, prelude::default, hierarchy::BuildChildren, render::{color::Color, view::Visibility}, text::{TextStyle, TextAlignment, Text}};
 
-use crate::components::ui::{UICompass, HexPosText, RightInfoPane, ButtonOnClick, HexTerrainText, HexNaniteText};
+use crate::{components::{ui::{UICompass, HexPosText, RightInfoPane, ButtonOnClick, HexTerrainText, HexNaniteText, HexStructureText, BuildFundsText, SimClockText, TimelineTrack, TimelineHandle, TimelineLiveButton, TimelineText, HeatmapLegend, HeatmapLegendTitle, HeatmapLegendSwatch, HeatmapLegendLabel, Minimap, MinimapViewport, MaccInfoPane, MaccInfoText, HexMaccList, HoverTooltip, HoverTooltipText, ChartsPanel, ChartsPanelButton, StatsChart, StatsChartText, HexSparkline, HexSparklineText}, structure::StructureKind, macc::MaccOrder}, resources::{sim_clock::SimSpeed, hex::MapState, stats::{StatsMetric, StatsHistory}}};
 
 use super::theme::{BOARDER_COLOR, BACKGROUND_COLOR, TEXT_COLOR};
 
+// Blank image for a chart to be plotted on, see plot_series
+fn chart_image(images: &mut Assets<Image>, width: u32, height: u32) -> Handle<Image> {
+    images.add(Image::new_fill(
+        Extent3d { width, height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb
+    ))
+}
+
 pub fn ui_setup(
-    mut commands: Commands
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>
 ) {
     //ROOT Node
     commands.spawn(NodeBundle {
@@ -70,6 +81,295 @@ pub fn ui_setup(
             });
         });
 
+        // Minimap, the image and its size are filled in once the map exists
+        root.spawn((ButtonBundle {
+            style: Style {
+                width: Val::Px(200.),
+                height: Val::Px(150.),
+                left: Val::Px(8.),
+                top: Val::Px(66.),
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(2.)),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            border_color: BOARDER_COLOR.into(),
+            ..default()
+        }, Minimap)).with_children(|minimap| {
+            // Camera View
+            minimap.spawn((NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                border_color: Color::WHITE.into(),
+                ..default()
+            }, MinimapViewport));
+        });
+
+        // Charts Panel
+        root.spawn((NodeBundle {
+            style: Style {
+                left: Val::Px(216.),
+                top: Val::Px(66.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(4.)),
+                border: UiRect::all(Val::Px(2.)),
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            border_color: BOARDER_COLOR.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        }, ChartsPanel)).with_children(|charts| {
+            charts.spawn(TextBundle::from_section(
+                format!("Last {} minutes", StatsHistory::WINDOW_MINUTES),
+                TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }
+            ));
+            for metric in StatsMetric::ALL {
+                charts.spawn((TextBundle::from_section(
+                    metric.to_string(),
+                    TextStyle {
+                        font_size: 14.0,
+                        color: TEXT_COLOR,
+                        ..default()
+                    }
+                ), StatsChartText(metric)));
+                charts.spawn((ImageBundle {
+                    style: Style {
+                        width: Val::Px(240.),
+                        height: Val::Px(60.),
+                        margin: UiRect::bottom(Val::Px(4.)),
+                        border: UiRect::all(Val::Px(1.)),
+                        ..default()
+                    },
+                    image: UiImage::new(chart_image(&mut images, 240, 60)),
+                    ..default()
+                }, StatsChart(metric)));
+            }
+        });
+
+        // Sim Controls
+        root.spawn(NodeBundle {
+            style: Style {
+                left: Val::Px(8.),
+                bottom: Val::Px(8.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(2.)),
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            border_color: BOARDER_COLOR.into(),
+            ..default()
+        }).with_children(|sim_controls| {
+            let buttons = [
+                ("||".to_string(), ButtonOnClick::SimPause),
+                (">|".to_string(), ButtonOnClick::SimStep)
+            ].into_iter().chain(SimSpeed::ALL.map(|speed| (speed.to_string(), ButtonOnClick::SimSpeed(speed))));
+            for (label, on_click) in buttons {
+                sim_controls.spawn((ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                        margin: UiRect::all(Val::Px(2.)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::WHITE.into(),
+                    border_color: BOARDER_COLOR.into(),
+                    ..default()
+                }, on_click))
+                .with_children(|sim_button| {
+                    sim_button.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 16.0,
+                            color: TEXT_COLOR,
+                            ..default()
+                        }
+                    ).with_text_alignment(TextAlignment::Center));
+                });
+            }
+
+            // Tick Counter
+            sim_controls.spawn((TextBundle {
+                text: Text::from_section("", TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }),
+                style: Style {
+                    margin: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+                    ..default()
+                },
+                ..default()
+            }, SimClockText));
+        });
+
+        // History Timeline
+        root.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(40.),
+                left: Val::Px(8.),
+                bottom: Val::Px(48.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(2.)),
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            border_color: BOARDER_COLOR.into(),
+            ..default()
+        }).with_children(|timeline| {
+            // Live Button
+            timeline.spawn((ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                    margin: UiRect::all(Val::Px(2.)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::WHITE.into(),
+                border_color: BOARDER_COLOR.into(),
+                ..default()
+            }, TimelineLiveButton))
+            .with_children(|live_button| {
+                live_button.spawn(TextBundle::from_section(
+                    "Live",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: TEXT_COLOR,
+                        ..default()
+                    }
+                ).with_text_alignment(TextAlignment::Center));
+            });
+
+            // Track
+            timeline.spawn((ButtonBundle {
+                style: Style {
+                    height: Val::Px(12.),
+                    flex_grow: 1.0,
+                    margin: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+                    ..default()
+                },
+                background_color: BOARDER_COLOR.into(),
+                ..default()
+            }, TimelineTrack))
+            .with_children(|track| {
+                // Handle
+                track.spawn((NodeBundle {
+                    style: Style {
+                        width: Val::Px(6.),
+                        height: Val::Px(16.),
+                        left: Val::Percent(100.),
+                        top: Val::Px(-2.),
+                        margin: UiRect::left(Val::Px(-3.)),
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    background_color: Color::WHITE.into(),
+                    ..default()
+                }, TimelineHandle));
+            });
+
+            // Viewed Tick
+            timeline.spawn((TextBundle {
+                text: Text::from_section("Live", TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }),
+                style: Style {
+                    min_width: Val::Px(80.),
+                    margin: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+                    ..default()
+                },
+                ..default()
+            }, TimelineText));
+        });
+
+        // Heatmap Legend
+        root.spawn((NodeBundle {
+            style: Style {
+                left: Val::Px(8.),
+                bottom: Val::Px(88.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(4.)),
+                border: UiRect::all(Val::Px(2.)),
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            border_color: BOARDER_COLOR.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        }, HeatmapLegend)).with_children(|legend| {
+            legend.spawn((TextBundle::from_section(
+                "Nanites",
+                TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }
+            ), HeatmapLegendTitle));
+
+            // Swatches
+            legend.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    margin: UiRect::vertical(Val::Px(4.)),
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                border_color: BOARDER_COLOR.into(),
+                ..default()
+            }).with_children(|swatches| {
+                for i in 0..16 {
+                    swatches.spawn((NodeBundle {
+                        style: Style {
+                            width: Val::Px(12.),
+                            height: Val::Px(12.),
+                            ..default()
+                        },
+                        ..default()
+                    }, HeatmapLegendSwatch((i as f32 + 0.5) / 16.0)));
+                }
+            });
+
+            // Min, Mid and Max Values
+            legend.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
+                ..default()
+            }).with_children(|labels| {
+                for t in [0.0, 0.5, 1.0] {
+                    labels.spawn((TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 14.0,
+                            color: TEXT_COLOR,
+                            ..default()
+                        }
+                    ), HeatmapLegendLabel(t)));
+                }
+            });
+        });
+
         //Spacer
         root.spawn(NodeBundle {
             style: Style {
@@ -182,6 +482,206 @@ pub fn ui_setup(
                         },
                         ..default()
                     }, HexNaniteText));
+
+                    //Hex Nanite History
+                    info_pane_content.spawn((TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 14.0,
+                            color: TEXT_COLOR,
+                            ..default()
+                        }
+                    ).with_text_alignment(TextAlignment::Center), HexSparklineText));
+                    info_pane_content.spawn((ImageBundle {
+                        style: Style {
+                            width: Val::Px(160.),
+                            height: Val::Px(30.),
+                            margin: UiRect::bottom(Val::Px(8.)),
+                            ..default()
+                        },
+                        image: UiImage::new(chart_image(&mut images, 160, 30)),
+                        ..default()
+                    }, HexSparkline));
+
+                    //Hex Structure Info
+                    info_pane_content.spawn((TextBundle {
+                        text: Text::from_section("", TextStyle {
+                            color: TEXT_COLOR,
+                            ..default()
+                        }).with_alignment(TextAlignment::Center),
+                        style: Style {
+                            margin: UiRect::axes(Val::Px(8.), Val::Px(12.)),
+                            ..default()
+                        },
+                        ..default()
+                    }, HexStructureText));
+
+                    //MACCs in the Hex
+                    info_pane_content.spawn((NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Stretch,
+                            margin: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        ..default()
+                    }, HexMaccList::default()));
+
+                    //Build Funds
+                    info_pane_content.spawn((TextBundle {
+                        text: Text::from_section("", TextStyle {
+                            color: TEXT_COLOR,
+                            ..default()
+                        }).with_alignment(TextAlignment::Center),
+                        style: Style {
+                            margin: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                            ..default()
+                        },
+                        ..default()
+                    }, BuildFundsText));
+
+                    //Build Buttons
+                    info_pane_content.spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Stretch,
+                            margin: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        ..default()
+                    }).with_children(|build_button_container| {
+                        for kind in [StructureKind::Emitter, StructureKind::Scrubber, StructureKind::Barrier] {
+                            build_button_container.spawn((ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                                    margin: UiRect::all(Val::Px(2.)),
+                                    border: UiRect::all(Val::Px(1.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: Color::WHITE.into(),
+                                border_color: BOARDER_COLOR.into(),
+                                ..default()
+                            }, ButtonOnClick::Build(kind)))
+                            .with_children(|build_button| {
+                                build_button.spawn(TextBundle::from_section(
+                                    format!("Build {} ({})", kind, kind.cost()),
+                                    TextStyle {
+                                        font_size: 16.0,
+                                        color: TEXT_COLOR,
+                                        ..default()
+                                    }
+                                ).with_text_alignment(TextAlignment::Center));
+                            });
+                        }
+                    });
+                });
+            });
+
+            // MACC Info
+            right_content.spawn((NodeBundle {
+                style: Style {
+                    width: Val::Auto,
+                    height: Val::Auto,
+                    min_width: Val::Percent(20.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                background_color: BOARDER_COLOR.into(),
+                ..default()
+            }, MaccInfoPane)
+            ).with_children(|macc_pane_content| {
+                macc_pane_content.spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        justify_content: JustifyContent::Start,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BACKGROUND_COLOR.into(),
+                    ..default()
+                }).with_children(|macc_pane_content| {
+                    //Close Button
+                    macc_pane_content.spawn((ButtonBundle {
+                        style: Style {
+                            width: Val::Px(25.),
+                            height: Val::Px(25.),
+                            left: Val::Px(4.),
+                            top: Val::Px(4.),
+                            position_type: PositionType::Absolute,
+                            border: UiRect::all(Val::Px(1.0)),
+                            justify_content: JustifyContent::Center,
+                            align_content: AlignContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        border_color: BOARDER_COLOR.into(),
+                        ..default()
+                    }, ButtonOnClick::MaccPaneClose))
+                    .with_children(|close_button| {
+                        close_button.spawn(TextBundle::from_section(
+                            "X", 
+                            TextStyle {
+                                color: TEXT_COLOR,
+                                ..default()
+                            }
+                        ).with_text_alignment(TextAlignment::Center));
+                    });
+
+                    // Unit Info
+                    for info_text in [MaccInfoText::Identity, MaccInfoText::Movement, MaccInfoText::Condition] {
+                        macc_pane_content.spawn((TextBundle {
+                            text: Text::from_section("", TextStyle {
+                                color: TEXT_COLOR,
+                                ..default()
+                            }).with_alignment(TextAlignment::Center),
+                            style: Style {
+                                margin: UiRect::axes(Val::Px(8.), Val::Px(12.)),
+                                ..default()
+                            },
+                            ..default()
+                        }, info_text));
+                    }
+
+                    //Command Buttons
+                    macc_pane_content.spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            margin: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        ..default()
+                    }).with_children(|command_button_container| {
+                        for order in [MaccOrder::Stop, MaccOrder::Hold, MaccOrder::Return] {
+                            command_button_container.spawn((ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                                    margin: UiRect::all(Val::Px(2.)),
+                                    border: UiRect::all(Val::Px(1.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: Color::WHITE.into(),
+                                border_color: BOARDER_COLOR.into(),
+                                ..default()
+                            }, ButtonOnClick::MaccOrder(order)))
+                            .with_children(|command_button| {
+                                command_button.spawn(TextBundle::from_section(
+                                    order.to_string(),
+                                    TextStyle {
+                                        font_size: 16.0,
+                                        color: TEXT_COLOR,
+                                        ..default()
+                                    }
+                                ).with_text_alignment(TextAlignment::Center));
+                            });
+                        }
+                    });
                 });
             });
 
@@ -198,7 +698,36 @@ pub fn ui_setup(
                 background_color: Color::RED.into(),
                 ..default()
             }).with_children(|map_button_container| {
-                // Terrain Button
+                // One Button per Overlay
+                for map_state in MapState::ALL {
+                    map_button_container.spawn((ButtonBundle {
+                        style: Style {
+                            min_width: Val::Px(25.),
+                            height: Val::Px(25.),
+                            padding: UiRect::horizontal(Val::Px(3.)),
+                            border: UiRect::all(Val::Px(1.0)),
+                            justify_content: JustifyContent::Center,
+                            align_content: AlignContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        border_color: BOARDER_COLOR.into(),
+                        ..default()
+                    }, ButtonOnClick::MapButton(map_state)))
+                    .with_children(|map_button| {
+                        map_button.spawn((TextBundle::from_section(
+                            format!("Alt+{}", map_state.shortcut()), 
+                            TextStyle {
+                                font_size: 14.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            }
+                        )).with_text_alignment(TextAlignment::Center));
+                    });
+                }
+
+                // Charts Button
                 map_button_container.spawn((ButtonBundle {
                     style: Style {
                         width: Val::Px(25.),
@@ -212,18 +741,18 @@ pub fn ui_setup(
                     background_color: Color::WHITE.into(),
                     border_color: BOARDER_COLOR.into(),
                     ..default()
-                }, ButtonOnClick::MapButtonTerrain))
-                .with_children(|close_button| {
-                    close_button.spawn((TextBundle::from_section(
-                        "T", 
+                }, ChartsPanelButton))
+                .with_children(|charts_button| {
+                    charts_button.spawn(TextBundle::from_section(
+                        "#", 
                         TextStyle {
-                            color: TEXT_COLOR.into(),
+                            color: TEXT_COLOR,
                             ..default()
                         }
-                    )).with_text_alignment(TextAlignment::Center));
+                    ).with_text_alignment(TextAlignment::Center));
                 });
 
-                // Nanite Button
+                // Flow Arrows Button
                 map_button_container.spawn((ButtonBundle {
                     style: Style {
                         width: Val::Px(25.),
@@ -237,17 +766,41 @@ pub fn ui_setup(
                     background_color: Color::WHITE.into(),
                     border_color: BOARDER_COLOR.into(),
                     ..default()
-                }, ButtonOnClick::MapButtonNanite))
-                .with_children(|close_button| {
-                    close_button.spawn((TextBundle::from_section(
-                        "N", 
+                }, ButtonOnClick::FlowOverlay))
+                .with_children(|flow_button| {
+                    flow_button.spawn(TextBundle::from_section(
+                        "~", 
                         TextStyle {
-                            color: TEXT_COLOR.into(),
+                            color: TEXT_COLOR,
                             ..default()
                         }
-                    )).with_text_alignment(TextAlignment::Center));
+                    ).with_text_alignment(TextAlignment::Center));
                 });
             });
         });
     });
+
+    // Hover Tooltip, placed and filled in while the cursor is over the map
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.)),
+            border: UiRect::all(Val::Px(1.)),
+            display: Display::None,
+            ..default()
+        },
+        background_color: BACKGROUND_COLOR.into(),
+        border_color: BOARDER_COLOR.into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    }, HoverTooltip)).with_children(|tooltip| {
+        tooltip.spawn((TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 14.0,
+                color: TEXT_COLOR,
+                ..default()
+            }
+        ), HoverTooltipText));
+    });
 }
\ No newline at end of file